@@ -0,0 +1,131 @@
+use crate::{ray::Point3, vec3::Vec3};
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Aabb {
+    /// Contains nothing, the identity for [`Aabb::union`].
+    pub const EMPTY: Self = Self {
+        min: Point3::splat(f32::INFINITY),
+        max: Point3::splat(f32::NEG_INFINITY),
+    };
+
+    pub fn new(a: Point3, b: Point3) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn grow(&self, p: Point3) -> Self {
+        Self {
+            min: self.min.min(p),
+            max: self.max.max(p),
+        }
+    }
+
+    /// Thickens the box to at least `delta` along every axis, so that flat primitives still have
+    /// some volume for the slab test to hit.
+    pub fn pad(&self, delta: f32) -> Self {
+        let e = |axis: usize| {
+            if self.max[axis] - self.min[axis] < delta {
+                delta / 2.
+            } else {
+                0.
+            }
+        };
+        let grow = Vec3::new(e(0), e(1), e(2));
+
+        Self {
+            min: self.min - grow,
+            max: self.max + grow,
+        }
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    /// Axis with the largest extent.
+    pub fn largest_axis(&self) -> usize {
+        let e = self.extent();
+
+        if e.x() > e.y() && e.x() > e.z() {
+            0
+        } else if e.y() > e.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let e = self.extent().max(Vec3::default());
+        2. * (e.x() * e.y() + e.y() * e.z() + e.z() * e.x())
+    }
+
+    /// Slab test. `inv_direction` is the component-wise inverse of the ray direction, computed
+    /// once per ray by the caller.
+    #[inline]
+    pub fn hit(&self, origin: Point3, inv_direction: Vec3, t_min: f32, t_max: f32) -> bool {
+        self.overlap(origin, inv_direction, t_min, t_max).is_some()
+    }
+
+    /// Where the ray is inside the box, as the range of `t` from where it enters to where it
+    /// leaves, clipped to `t_min..t_max`.
+    #[inline]
+    pub fn overlap(
+        &self,
+        origin: Point3,
+        inv_direction: Vec3,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<(f32, f32)> {
+        let t0 = (self.min - origin) * inv_direction;
+        let t1 = (self.max - origin) * inv_direction;
+
+        let near = t0.min(t1);
+        let far = t0.max(t1);
+
+        let t_enter = near.x().max(near.y()).max(near.z()).max(t_min);
+        let t_exit = far.x().min(far.y()).min(far.z()).min(t_max);
+
+        (t_enter <= t_exit).then_some((t_enter, t_exit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Aabb;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn slab_test() {
+        let b = Aabb::new(Vec3::new(-1., -1., -1.), Vec3::new(1., 1., 1.));
+        let inv = |d: Vec3| Vec3::new(1. / d.x(), 1. / d.y(), 1. / d.z());
+
+        let origin = Vec3::new(0., 0., -5.);
+        assert!(b.hit(origin, inv(Vec3::new(0., 0., 1.)), 0., f32::INFINITY));
+        assert!(!b.hit(origin, inv(Vec3::new(0., 0., -1.)), 0., f32::INFINITY));
+        assert!(!b.hit(origin, inv(Vec3::new(0., 0., 1.)), 0., 3.));
+        assert!(!b.hit(origin, inv(Vec3::new(0., 1., 1.)), 0., f32::INFINITY));
+
+        assert_eq!(b.surface_area(), 24.);
+        assert_eq!(Aabb::EMPTY.union(&b), b);
+    }
+}
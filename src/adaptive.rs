@@ -0,0 +1,81 @@
+//! Running statistics of a pixel's samples, to tell when it has taken enough of them.
+
+use crate::vec3::Color;
+
+/// Luminance that darker pixels are measured against, so that noise too faint to see doesn't
+/// keep nearly black pixels sampling.
+const DARK: f64 = 0.01;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PixelEstimate {
+    sum: Color,
+    samples: u32,
+    /// Mean of the samples' luminance, and the sum of their squared deviations from it, updated
+    /// one sample at a time (Welford, 1962).
+    mean: f64,
+    m2: f64,
+}
+
+impl PixelEstimate {
+    pub fn add(&mut self, sample: Color) {
+        self.sum += sample;
+        self.samples += 1;
+
+        let luminance = sample.luminance() as f64;
+        let delta = luminance - self.mean;
+        self.mean += delta / self.samples as f64;
+        self.m2 += delta * (luminance - self.mean);
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Mean of the samples, black without any.
+    pub fn color(&self) -> Color {
+        if self.samples == 0 {
+            return Color::default();
+        }
+
+        self.sum / self.samples as f32
+    }
+
+    /// Standard error of the mean luminance, relative to the mean. Infinite until there are two
+    /// samples to estimate the variance from.
+    pub fn relative_error(&self) -> f32 {
+        if self.samples < 2 {
+            return f32::INFINITY;
+        }
+
+        let n = self.samples as f64;
+        let variance = self.m2 / (n - 1.);
+        ((variance / n).sqrt() / self.mean.max(DARK)) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PixelEstimate;
+    use crate::vec3::Color;
+
+    #[test]
+    fn running_statistics() {
+        let mut estimate = PixelEstimate::default();
+        assert_eq!(estimate.color(), Color::default());
+        assert_eq!(estimate.relative_error(), f32::INFINITY);
+
+        for l in [1., 2., 3., 4.] {
+            estimate.add(Color::new(l, l, l));
+        }
+        assert_eq!(estimate.samples(), 4);
+        assert_eq!(estimate.color(), Color::new(2.5, 2.5, 2.5));
+        // Variance 5/3, so the standard error is sqrt(5/12) against a mean of 2.5.
+        let expected = (5f32 / 12.).sqrt() / 2.5;
+        assert!((estimate.relative_error() - expected).abs() < 1e-5);
+
+        let mut flat = PixelEstimate::default();
+        flat.add(Color::new(0.5, 0.5, 0.5));
+        flat.add(Color::new(0.5, 0.5, 0.5));
+        assert_eq!(flat.relative_error(), 0.);
+    }
+}
@@ -0,0 +1,36 @@
+use crate::vec3::{Color, Vec3};
+
+/// Radiance of rays that escape the scene.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Background {
+    Solid(Color),
+    /// Blends from `bottom` to `top` with the height of the ray direction.
+    Gradient {
+        bottom: Color,
+        top: Color,
+    },
+}
+
+impl Background {
+    pub const BLACK: Self = Background::Solid(Color::splat(0.));
+
+    pub fn color(&self, direction: Vec3) -> Color {
+        match *self {
+            Background::Solid(c) => c,
+            Background::Gradient { bottom, top } => {
+                let t = (direction.unit_vector().y() + 1.) * 0.5;
+                bottom.mul_add_vec(1. - t, top * t)
+            }
+        }
+    }
+}
+
+impl Default for Background {
+    /// The sky of _Ray Tracing in One Weekend_.
+    fn default() -> Self {
+        Background::Gradient {
+            bottom: Color::new(1., 1., 1.),
+            top: Color::new(0.5, 0.7, 1.),
+        }
+    }
+}
@@ -0,0 +1,1053 @@
+//! Bidirectional scattering distribution functions, which say how much of the light arriving at
+//! a point from one direction leaves it in another.
+//!
+//! [`Bxdf`]s work in a local shading frame, with the surface normal along z and the tangent of
+//! the surface's `u` parameter along x. Both of their directions are unit vectors that point
+//! away from the surface: `wo` towards where the light leaves to, like the viewer, and `wi`
+//! towards where it arrives from. A [`Bsdf`] places one at a hit, in world space.
+
+use crate::{
+    microfacet::{self, Ggx, Gtr1},
+    onb::Onb,
+    sampler::{self, ONE_MINUS_EPSILON},
+    vec3::{Color, Vec3},
+};
+
+use std::{f32::consts::PI, ops::BitOr};
+
+/// Kinds of scattering, which the lobes of BSDFs combine.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Flags(u8);
+
+impl Flags {
+    pub const REFLECTION: Self = Self(1);
+    pub const TRANSMISSION: Self = Self(1 << 1);
+    pub const DIFFUSE: Self = Self(1 << 2);
+    pub const GLOSSY: Self = Self(1 << 3);
+    /// Scattering into a few discrete directions only, like mirrors and smooth glass do, which
+    /// can't be evaluated for any other direction.
+    pub const SPECULAR: Self = Self(1 << 4);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_specular(self) -> bool {
+        self.contains(Self::SPECULAR)
+    }
+
+    /// Whether some lobe spreads light over a range of directions, which light sampling can
+    /// find.
+    pub fn is_non_specular(self) -> bool {
+        self.0 & (Self::DIFFUSE.0 | Self::GLOSSY.0) != 0
+    }
+}
+
+impl BitOr for Flags {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+/// A direction picked by [`Bxdf::sample`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BsdfSample {
+    pub wi: Vec3,
+    /// What [`Bxdf::eval`] gives for `wi`, or for specular lobes the fraction of the light
+    /// that `wi` brings.
+    pub f: Color,
+    /// Solid angle density of picking `wi`, or for specular lobes its probability.
+    pub pdf: f32,
+    /// The kind of lobe `wi` comes from.
+    pub flags: Flags,
+}
+
+impl BsdfSample {
+    /// How much the sample scales the light it brings, as an estimate of the light from all
+    /// directions.
+    pub fn weight(&self) -> Color {
+        self.f / self.pdf
+    }
+}
+
+pub trait Bxdf {
+    /// The kinds of lobes it has.
+    fn flags(&self) -> Flags;
+
+    /// BSDF times the cosine between `wi` and the normal, for light arriving from `wi` and
+    /// leaving along `wo`. Black for specular lobes, which only `sample` finds.
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Color;
+
+    /// A direction `wi` for light leaving along `wo`, picked by the sample values `uc` and `u`,
+    /// ideally in proportion to `eval`.
+    fn sample(&self, wo: Vec3, uc: f32, u: (f32, f32)) -> Option<BsdfSample>;
+
+    /// Solid angle density of `sample` picking `wi`, 0 for specular lobes.
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f32;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bxdfs {
+    Diffuse(DiffuseBxdf),
+    Conductor(ConductorBxdf),
+    Dielectric(DielectricBxdf),
+    Principled(PrincipledBxdf),
+    HenyeyGreenstein(HenyeyGreenstein),
+}
+
+impl Bxdf for Bxdfs {
+    fn flags(&self) -> Flags {
+        match self {
+            Bxdfs::Diffuse(d) => d.flags(),
+            Bxdfs::Conductor(c) => c.flags(),
+            Bxdfs::Dielectric(d) => d.flags(),
+            Bxdfs::Principled(p) => p.flags(),
+            Bxdfs::HenyeyGreenstein(h) => h.flags(),
+        }
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Color {
+        match self {
+            Bxdfs::Diffuse(d) => d.eval(wo, wi),
+            Bxdfs::Conductor(c) => c.eval(wo, wi),
+            Bxdfs::Dielectric(d) => d.eval(wo, wi),
+            Bxdfs::Principled(p) => p.eval(wo, wi),
+            Bxdfs::HenyeyGreenstein(h) => h.eval(wo, wi),
+        }
+    }
+
+    fn sample(&self, wo: Vec3, uc: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        match self {
+            Bxdfs::Diffuse(d) => d.sample(wo, uc, u),
+            Bxdfs::Conductor(c) => c.sample(wo, uc, u),
+            Bxdfs::Dielectric(d) => d.sample(wo, uc, u),
+            Bxdfs::Principled(p) => p.sample(wo, uc, u),
+            Bxdfs::HenyeyGreenstein(h) => h.sample(wo, uc, u),
+        }
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f32 {
+        match self {
+            Bxdfs::Diffuse(d) => d.pdf(wo, wi),
+            Bxdfs::Conductor(c) => c.pdf(wo, wi),
+            Bxdfs::Dielectric(d) => d.pdf(wo, wi),
+            Bxdfs::Principled(p) => p.pdf(wo, wi),
+            Bxdfs::HenyeyGreenstein(h) => h.pdf(wo, wi),
+        }
+    }
+}
+
+impl From<DiffuseBxdf> for Bxdfs {
+    fn from(d: DiffuseBxdf) -> Self {
+        Bxdfs::Diffuse(d)
+    }
+}
+
+impl From<ConductorBxdf> for Bxdfs {
+    fn from(c: ConductorBxdf) -> Self {
+        Bxdfs::Conductor(c)
+    }
+}
+
+impl From<DielectricBxdf> for Bxdfs {
+    fn from(d: DielectricBxdf) -> Self {
+        Bxdfs::Dielectric(d)
+    }
+}
+
+impl From<PrincipledBxdf> for Bxdfs {
+    fn from(p: PrincipledBxdf) -> Self {
+        Bxdfs::Principled(p)
+    }
+}
+
+impl From<HenyeyGreenstein> for Bxdfs {
+    fn from(h: HenyeyGreenstein) -> Self {
+        Bxdfs::HenyeyGreenstein(h)
+    }
+}
+
+/// A [`Bxdf`] at a hit, which takes and gives directions in world space. They don't need to
+/// be unit vectors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bsdf {
+    frame: Onb,
+    bxdf: Bxdfs,
+}
+
+impl Bsdf {
+    /// With the shading frame's z axis along `normal`, and its x axis along `tangent`.
+    pub fn new(normal: Vec3, tangent: Vec3, bxdf: impl Into<Bxdfs>) -> Self {
+        Self {
+            frame: Onb::from_w_and_u(normal, tangent),
+            bxdf: bxdf.into(),
+        }
+    }
+
+    pub fn flags(&self) -> Flags {
+        self.bxdf.flags()
+    }
+
+    pub fn eval(&self, wo: Vec3, wi: Vec3) -> Color {
+        self.bxdf.eval(self.to_local(wo), self.to_local(wi))
+    }
+
+    pub fn sample(&self, wo: Vec3, uc: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        let sample = self.bxdf.sample(self.to_local(wo), uc, u)?;
+
+        Some(BsdfSample {
+            wi: self.frame.local(sample.wi),
+            ..sample
+        })
+    }
+
+    pub fn pdf(&self, wo: Vec3, wi: Vec3) -> f32 {
+        self.bxdf.pdf(self.to_local(wo), self.to_local(wi))
+    }
+
+    fn to_local(self, w: Vec3) -> Vec3 {
+        self.frame.to_local(w.unit_vector())
+    }
+}
+
+/// Diffuse reflection: Lambertian, the same in every direction, or rough with Oren and Nayar's
+/// V-shaped grooves, which look flatter than Lambertian surfaces and reflect more light back
+/// towards where it comes from, like clay, cloth and concrete.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffuseBxdf {
+    reflectance: Color,
+    a: f32,
+    b: f32,
+}
+
+impl DiffuseBxdf {
+    pub fn new(reflectance: Color) -> Self {
+        Self::oren_nayar(reflectance, 0.)
+    }
+
+    /// `sigma` is the standard deviation, in radians, of the angle of the grooves' sides with
+    /// the surface. It's Lambertian for 0.
+    pub fn oren_nayar(reflectance: Color, sigma: f32) -> Self {
+        let sigma2 = sigma * sigma;
+
+        Self {
+            reflectance,
+            a: 1. - sigma2 / (2. * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+}
+
+impl Bxdf for DiffuseBxdf {
+    fn flags(&self) -> Flags {
+        Flags::REFLECTION | Flags::DIFFUSE
+    }
+
+    /// `reflectance / PI` times the cosine for Lambertian surfaces, which is the reflectance
+    /// times the density. Grooves scale it by how far and how much towards `wo` light arrives.
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Color {
+        if wi.z() <= 0. {
+            return Color::default();
+        }
+
+        let mut scale = self.a;
+        let sin_i = (1. - wi.z() * wi.z()).max(0.).sqrt();
+        let sin_o = (1. - wo.z() * wo.z()).max(0.).sqrt();
+        if self.b > 0. && sin_i > 1e-4 && sin_o > 1e-4 {
+            let cos_phi = ((wi.x() * wo.x() + wi.y() * wo.y()) / (sin_i * sin_o)).max(0.);
+            // Sine of the larger of the two angles with the normal, and tangent of the smaller.
+            let (sin_alpha, tan_beta) = if wi.z() > wo.z().abs() {
+                (sin_o, sin_i / wi.z())
+            } else {
+                (sin_i, sin_o / wo.z().abs())
+            };
+            scale += self.b * cos_phi * sin_alpha * tan_beta;
+        }
+
+        self.reflectance * (scale * self.pdf(wo, wi))
+    }
+
+    fn sample(&self, wo: Vec3, _uc: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        let wi = sampler::cosine_hemisphere(u);
+        if wi.z() <= 0. {
+            return None;
+        }
+
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, wi),
+            pdf: self.pdf(wo, wi),
+            flags: self.flags(),
+        })
+    }
+
+    /// Cosine weighted, like Lambertian reflection.
+    fn pdf(&self, _wo: Vec3, wi: Vec3) -> f32 {
+        wi.z().max(0.) / PI
+    }
+}
+
+/// How much light a conductor reflects, depending on the cosine of the angle it arrives at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fresnel {
+    /// The color at normal incidence, which grazing angles turn white by Schlick's
+    /// approximation.
+    Schlick(Color),
+    /// Complex index of refraction `eta + ik`, per channel.
+    Conductor { eta: Color, k: Color },
+}
+
+impl Fresnel {
+    pub fn reflectance(self, cos_theta: f32) -> Color {
+        match self {
+            Fresnel::Schlick(f0) => microfacet::fresnel_schlick(cos_theta, f0),
+            Fresnel::Conductor { eta, k } => microfacet::fresnel_conductor(cos_theta, eta, k),
+        }
+    }
+}
+
+/// A mirror, or rough with GGX microfacets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConductorBxdf {
+    fresnel: Fresnel,
+    roughness: Ggx,
+}
+
+impl ConductorBxdf {
+    pub fn new(fresnel: Fresnel, roughness: Ggx) -> Self {
+        Self { fresnel, roughness }
+    }
+
+    /// Value and density of the reflection by the microfacet `wm`, the Torrance-Sparrow model:
+    /// `D G F / (4 cos θo cos θi)`, times `cos θi`, and the density of the visible normal
+    /// turned into one of reflected directions.
+    fn reflection(&self, wo: Vec3, wi: Vec3, wm: Vec3) -> (Color, f32) {
+        let d = self.roughness.d(wm);
+        let g = self.roughness.g(wo, wi);
+        let f = self.fresnel.reflectance(wo.dot(wm)) * (d * g / (4. * wo.z()));
+
+        (f, self.roughness.visible_pdf(wo, wm) / (4. * wo.dot(wm)))
+    }
+
+    /// The microfacet normal that reflects `wo` into `wi`, if both are above the surface.
+    fn half_vector(wo: Vec3, wi: Vec3) -> Option<Vec3> {
+        let wm = wo + wi;
+        (wo.z() > 0. && wi.z() > 0. && wm.len_squared() > 0.).then(|| wm.unit_vector())
+    }
+}
+
+impl Bxdf for ConductorBxdf {
+    fn flags(&self) -> Flags {
+        if self.roughness.is_smooth() {
+            Flags::REFLECTION | Flags::SPECULAR
+        } else {
+            Flags::REFLECTION | Flags::GLOSSY
+        }
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Color {
+        if self.roughness.is_smooth() {
+            return Color::default();
+        }
+
+        Self::half_vector(wo, wi).map_or(Color::default(), |wm| self.reflection(wo, wi, wm).0)
+    }
+
+    fn sample(&self, wo: Vec3, _uc: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        if self.roughness.is_smooth() {
+            return Some(BsdfSample {
+                wi: Vec3::new(-wo.x(), -wo.y(), wo.z()),
+                f: self.fresnel.reflectance(wo.z()),
+                pdf: 1.,
+                flags: self.flags(),
+            });
+        }
+
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        let wm = self.roughness.sample_visible(wo, u);
+        let wi = (-wo).reflect(wm);
+        if wi.z() <= 0. {
+            return None;
+        }
+
+        let (f, pdf) = self.reflection(wo, wi, wm);
+        Some(BsdfSample {
+            wi,
+            f,
+            pdf,
+            flags: self.flags(),
+        })
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f32 {
+        if self.roughness.is_smooth() {
+            return 0.;
+        }
+
+        Self::half_vector(wo, wi).map_or(0., |wm| self.reflection(wo, wi, wm).1)
+    }
+}
+
+/// The boundary of glass, water and the like: smooth, or rough like frosted glass with GGX
+/// microfacets (Walter et al., "Microfacet Models for Refraction through Rough Surfaces",
+/// 2007).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DielectricBxdf {
+    /// Ratio of the index of refraction below the surface to the one above it.
+    eta: f32,
+    roughness: Ggx,
+    /// Color light is filtered by when it goes through.
+    tint: Color,
+    /// Color all light is filtered by, whichever way it goes on, like what's left of it after
+    /// going through an absorbing inside.
+    transmittance: Color,
+}
+
+impl DielectricBxdf {
+    pub fn new(eta: f32, roughness: Ggx, tint: Color, transmittance: Color) -> Self {
+        Self {
+            eta,
+            roughness,
+            tint,
+            transmittance,
+        }
+    }
+
+    /// The microfacet normal that scatters `wo` into `wi`, with the reflection or refraction
+    /// through the surface it takes. `None` when no microfacet facing `wo` does.
+    fn half_vector(&self, wo: Vec3, wi: Vec3) -> Option<(Vec3, bool)> {
+        let reflect = wi.z() > 0.;
+        let wm = if reflect { wo + wi } else { wo + wi * self.eta };
+        if wo.z() <= 0. || wi.z() == 0. || wm.len_squared() == 0. {
+            return None;
+        }
+
+        let wm = wm.unit_vector() * wm.z().signum();
+        // Refracted light leaves on the far side of the microfacet.
+        let back_facing = wo.dot(wm) <= 0. || (wi.dot(wm) < 0.) == reflect;
+
+        (!back_facing).then_some((wm, reflect))
+    }
+
+    /// Value and density of the reflection or refraction by the microfacet `wm`. Refracted
+    /// radiance should also be scaled by `1 / η²`, as the same light gets squeezed into a
+    /// narrower or wider cone, but that is deliberately left out: a path that enters a closed
+    /// object has to leave it too, and the factors of the two crossings cancel.
+    fn scattering(&self, wo: Vec3, wi: Vec3, wm: Vec3, reflect: bool) -> (Color, f32) {
+        let d = self.roughness.d(wm);
+        let g = self.roughness.g(wo, wi);
+        let fresnel = microfacet::fresnel_dielectric(wo.dot(wm), self.eta);
+        let visible = self.roughness.visible_pdf(wo, wm);
+
+        if reflect {
+            let f = self.transmittance * (d * g * fresnel / (4. * wo.z()));
+            (f, visible / (4. * wo.dot(wm)) * fresnel)
+        } else {
+            // Jacobian of the refracted direction with respect to the microfacet normal.
+            let denom = (wi.dot(wm) + wo.dot(wm) / self.eta).powi(2);
+            let jacobian = wi.dot(wm).abs() / denom;
+            let f = self.transmittance
+                * self.tint
+                * (d * (1. - fresnel) * g * jacobian * wo.dot(wm).abs() / wo.z());
+            (f, visible * jacobian * (1. - fresnel))
+        }
+    }
+}
+
+impl Bxdf for DielectricBxdf {
+    fn flags(&self) -> Flags {
+        let lobe = if self.roughness.is_smooth() {
+            Flags::SPECULAR
+        } else {
+            Flags::GLOSSY
+        };
+
+        Flags::REFLECTION | Flags::TRANSMISSION | lobe
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Color {
+        if self.roughness.is_smooth() {
+            return Color::default();
+        }
+
+        self.half_vector(wo, wi)
+            .map_or(Color::default(), |(wm, reflect)| {
+                self.scattering(wo, wi, wm, reflect).0
+            })
+    }
+
+    /// Reflection or refraction, in proportion to the Fresnel reflectance of the surface, or of
+    /// a visible microfacet.
+    fn sample(&self, wo: Vec3, uc: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        if self.roughness.is_smooth() {
+            let fresnel = microfacet::fresnel_dielectric(wo.z(), self.eta);
+            let normal = Vec3::new(0., 0., 1.);
+
+            return Some(if uc < fresnel {
+                BsdfSample {
+                    wi: (-wo).reflect(normal),
+                    f: self.transmittance * fresnel,
+                    pdf: fresnel,
+                    flags: Flags::REFLECTION | Flags::SPECULAR,
+                }
+            } else {
+                BsdfSample {
+                    wi: (-wo).refract(normal, 1. / self.eta),
+                    f: self.transmittance * self.tint * (1. - fresnel),
+                    pdf: 1. - fresnel,
+                    flags: Flags::TRANSMISSION | Flags::SPECULAR,
+                }
+            });
+        }
+
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        let wm = self.roughness.sample_visible(wo, u);
+        let reflect = uc < microfacet::fresnel_dielectric(wo.dot(wm), self.eta);
+        let wi = if reflect {
+            (-wo).reflect(wm)
+        } else {
+            (-wo).refract(wm, 1. / self.eta)
+        };
+        if (wi.z() > 0.) != reflect || wi.z() == 0. {
+            return None;
+        }
+
+        let (f, pdf) = self.scattering(wo, wi, wm, reflect);
+        let lobe = if reflect {
+            Flags::REFLECTION
+        } else {
+            Flags::TRANSMISSION
+        };
+        Some(BsdfSample {
+            wi,
+            f,
+            pdf,
+            flags: lobe | Flags::GLOSSY,
+        })
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f32 {
+        if self.roughness.is_smooth() {
+            return 0.;
+        }
+
+        self.half_vector(wo, wi)
+            .map_or(0., |(wm, reflect)| self.scattering(wo, wi, wm, reflect).1)
+    }
+}
+
+/// The lobes of Disney's principled BSDF, see [`crate::material::Principled`]: a diffuse one
+/// with sheen, a specular one, a glass one and a clearcoat.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrincipledBxdf {
+    base: Color,
+    /// Perceptual roughness of the diffuse lobe.
+    roughness: f32,
+    sheen: Color,
+    specular: ConductorBxdf,
+    glass: DielectricBxdf,
+    clearcoat: Gtr1,
+    /// What the diffuse, specular, glass and clearcoat lobes are worth, in that order.
+    weights: [f32; 4],
+}
+
+impl PrincipledBxdf {
+    pub fn new(
+        base: Color,
+        roughness: f32,
+        sheen: Color,
+        specular: ConductorBxdf,
+        glass: DielectricBxdf,
+        clearcoat: Gtr1,
+        weights: [f32; 4],
+    ) -> Self {
+        Self {
+            base,
+            roughness,
+            sheen,
+            specular,
+            glass,
+            clearcoat,
+            weights,
+        }
+    }
+
+    /// Odds of sampling each lobe, in the order of the weights. Specular ones go by how much
+    /// they reflect towards `wo`, though even the darkest keep a few samples since they turn
+    /// white at grazing angles.
+    fn probabilities(&self, wo: Vec3) -> [f32; 4] {
+        let [diffuse, specular, glass, clearcoat] = self.weights;
+        let reflectance = self.specular.fresnel.reflectance(wo.z()).luminance();
+        let p = [
+            diffuse,
+            specular * reflectance.max(0.1),
+            glass,
+            clearcoat * (0.04 + 0.96 * schlick_weight(wo.z())),
+        ];
+
+        let sum: f32 = p.iter().sum();
+        if sum > 0. {
+            p.map(|p| p / sum)
+        } else {
+            p
+        }
+    }
+
+    /// Burley's diffuse, which rough surfaces brighten and smooth ones darken at grazing
+    /// angles, plus the sheen, times the cosine.
+    fn diffuse(&self, wo: Vec3, wi: Vec3) -> Color {
+        let cos_d = wi.dot((wo + wi).unit_vector());
+        let fd90 = 0.5 + 2. * self.roughness * cos_d * cos_d;
+        let fd = (1. + (fd90 - 1.) * schlick_weight(wo.z()))
+            * (1. + (fd90 - 1.) * schlick_weight(wi.z()));
+
+        (self.base * (fd / PI) + self.sheen * schlick_weight(cos_d)) * wi.z()
+    }
+
+    /// The clearcoat's BSDF times the cosine, with a reflectance of 4% at normal incidence,
+    /// and its density. Its microfacets are masked as GGX ones of a fixed roughness.
+    fn clearcoat(&self, wo: Vec3, wi: Vec3) -> (f32, f32) {
+        let wm = (wo + wi).unit_vector();
+        let d = self.clearcoat.d(wm);
+        let g = Ggx::from_roughness(0.5, 0.5).g(wo, wi);
+        let f = 0.04 + 0.96 * schlick_weight(wo.dot(wm));
+
+        (d * g * f / (4. * wo.z()), d * wm.z() / (4. * wo.dot(wm)))
+    }
+}
+
+impl Bxdf for PrincipledBxdf {
+    fn flags(&self) -> Flags {
+        let mut flags = Flags::REFLECTION | Flags::DIFFUSE | Flags::GLOSSY;
+        if self.weights[2] > 0. {
+            flags = flags | Flags::TRANSMISSION;
+        }
+
+        flags
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Color {
+        let [diffuse, specular, glass, clearcoat] = self.weights;
+
+        let mut f = self.specular.eval(wo, wi) * specular;
+        if glass > 0. {
+            f += self.glass.eval(wo, wi) * glass;
+        }
+        if wo.z() > 0. && wi.z() > 0. {
+            f += self.diffuse(wo, wi) * diffuse;
+            if clearcoat > 0. {
+                f += Color::new(1., 1., 1.) * (self.clearcoat(wo, wi).0 * clearcoat);
+            }
+        }
+
+        f
+    }
+
+    /// Samples one lobe, picked with `uc`, and weighs the direction by the density of all of
+    /// them finding it.
+    fn sample(&self, wo: Vec3, uc: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        // What's left of `uc` once the lobe is picked is as uniform, for the glass to choose
+        // between reflection and refraction.
+        let p = self.probabilities(wo);
+        let (mut lobe, mut uc) = (0, uc);
+        while lobe < p.len() - 1 && (uc >= p[lobe] || p[lobe] == 0.) {
+            uc -= p[lobe];
+            lobe += 1;
+        }
+        if p[lobe] == 0. {
+            return None;
+        }
+        let uc = (uc / p[lobe]).clamp(0., ONE_MINUS_EPSILON);
+
+        let (wi, flags) = match lobe {
+            0 => {
+                let diffuse = DiffuseBxdf::new(self.base).sample(wo, uc, u)?;
+                (diffuse.wi, diffuse.flags)
+            }
+            1 => {
+                let specular = self.specular.sample(wo, uc, u)?;
+                (specular.wi, specular.flags)
+            }
+            2 => {
+                let glass = self.glass.sample(wo, uc, u)?;
+                (glass.wi, glass.flags)
+            }
+            _ => {
+                let wi = (-wo).reflect(self.clearcoat.sample(u));
+                if wi.z() <= 0. {
+                    return None;
+                }
+                (wi, Flags::REFLECTION | Flags::GLOSSY)
+            }
+        };
+
+        let pdf = self.pdf(wo, wi);
+        (pdf > 0.).then(|| BsdfSample {
+            wi,
+            f: self.eval(wo, wi),
+            pdf,
+            flags,
+        })
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f32 {
+        let [diffuse, specular, glass, clearcoat] = self.probabilities(wo);
+
+        let mut pdf = specular * self.specular.pdf(wo, wi);
+        if glass > 0. {
+            pdf += glass * self.glass.pdf(wo, wi);
+        }
+        if wo.z() > 0. && wi.z() > 0. {
+            pdf += diffuse * wi.z() / PI;
+            if clearcoat > 0. {
+                pdf += clearcoat * self.clearcoat(wo, wi).1;
+            }
+        }
+
+        pdf
+    }
+}
+
+/// How much grazing angles, with cosines towards 0, turn reflections white by Schlick's
+/// approximation.
+fn schlick_weight(cos_theta: f32) -> f32 {
+    (1. - cos_theta.clamp(0., 1.)).powi(5)
+}
+
+/// The Henyey–Greenstein phase function of participating media, which is isotropic for
+/// `g = 0`, scatters forward for positive `g` and backward for negative `g`. Only the angle
+/// between `wo` and `wi` matters, and there is no cosine.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HenyeyGreenstein {
+    albedo: Color,
+    g: f32,
+}
+
+impl HenyeyGreenstein {
+    /// `g` is the average cosine between the ray and the scattered direction, in (-1, 1).
+    pub fn new(albedo: Color, g: f32) -> Self {
+        Self {
+            albedo,
+            g: g.clamp(-0.99, 0.99),
+        }
+    }
+
+    /// Density of scattering with a cosine of `cos_theta` between the ray, along `-wo`, and
+    /// `wi`.
+    fn density(&self, cos_theta: f32) -> f32 {
+        let g = self.g;
+        let denom = 1. + g * g - 2. * g * cos_theta;
+
+        (1. - g * g) / (4. * PI * denom * denom.sqrt())
+    }
+}
+
+impl Bxdf for HenyeyGreenstein {
+    /// Media scatter light all around, with nothing specular about it.
+    fn flags(&self) -> Flags {
+        Flags::REFLECTION | Flags::TRANSMISSION | Flags::DIFFUSE
+    }
+
+    /// The albedo times the density.
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Color {
+        self.albedo * self.pdf(wo, wi)
+    }
+
+    fn sample(&self, wo: Vec3, _uc: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        let g = self.g;
+        let xi = u.0;
+        let cos_theta = if g.abs() < 1e-3 {
+            1. - 2. * xi
+        } else {
+            let s = (1. - g * g) / (1. - g + 2. * g * xi);
+            (1. + g * g - s * s) / (2. * g)
+        };
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = 2. * PI * u.1;
+
+        let wi = Onb::from_w(-wo).local(Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ));
+
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, wi),
+            pdf: self.pdf(wo, wi),
+            flags: self.flags(),
+        })
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f32 {
+        self.density((-wo).dot(wi).clamp(-1., 1.))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Bxdf, Bxdfs, ConductorBxdf, DielectricBxdf, DiffuseBxdf, Flags, Fresnel, HenyeyGreenstein,
+        PrincipledBxdf,
+    };
+    use crate::{
+        microfacet::{Ggx, Gtr1},
+        sampler::{cosine_hemisphere, uniform_sphere, Sampler, Sampling},
+        vec3::{Color, Vec3},
+    };
+    use std::f32::consts::PI;
+
+    use fastrand::Rng;
+
+    /// Checks the samples of `bxdf`, for light leaving along `wo`, against its `eval` and
+    /// `pdf`: each sample has the value and density they give, and integrating them over the
+    /// sphere agrees with averaging samples. Integrated densities are the odds of getting a
+    /// sample at all, and the mean sampled direction is the density's, which wouldn't be if
+    /// `sample` picked directions with another density than `pdf` says.
+    ///
+    /// Uniform directions come from a Sobol sampler, which keeps sharp lobes from making the
+    /// integrals noisy, and sums are in `f64`, since adding that many small `f32`s loses too
+    /// much precision.
+    fn assert_samples_match(bxdf: impl Into<Bxdfs>, wo: Vec3, rng: &Rng) {
+        let bxdf = bxdf.into();
+        let n = 400_000;
+        let add = |sum: &mut [f64; 7], values: [f32; 7]| {
+            for (s, v) in sum.iter_mut().zip(values) {
+                *s += v as f64 / n as f64;
+            }
+        };
+
+        let mut sampler = Sampling::Sobol.sampler(rng.u64(..), n);
+        let mut integrated = [0.; 7];
+        for i in 0..n {
+            sampler.start_pixel_sample(0, i);
+            let wi = uniform_sphere(sampler.get_2d());
+            let f = bxdf.eval(wo, wi) * (4. * PI);
+            let pdf = bxdf.pdf(wo, wi) * (4. * PI);
+            let mean = wi * pdf;
+            add(
+                &mut integrated,
+                [f.x(), f.y(), f.z(), pdf, mean.x(), mean.y(), mean.z()],
+            );
+        }
+
+        let mut sampled = [0.; 7];
+        for _ in 0..n {
+            let Some(sample) = bxdf.sample(wo, rng.f32(), (rng.f32(), rng.f32())) else {
+                continue;
+            };
+            assert!(!sample.flags.is_specular());
+            assert!((sample.wi.len() - 1.).abs() < 1e-3, "{sample:?}");
+
+            let pdf = bxdf.pdf(wo, sample.wi);
+            assert!(
+                (sample.pdf - pdf).abs() <= 1e-3 * pdf.max(1.),
+                "{sample:?} vs {pdf}"
+            );
+            let f = bxdf.eval(wo, sample.wi);
+            assert!(
+                (sample.f - f).len() <= 1e-3 * f.len().max(1.),
+                "{sample:?} vs {f:?}"
+            );
+
+            let w = sample.weight();
+            let wi = sample.wi;
+            add(
+                &mut sampled,
+                [w.x(), w.y(), w.z(), 1., wi.x(), wi.y(), wi.z()],
+            );
+        }
+
+        for (i, s) in integrated.iter().zip(sampled) {
+            assert!((i - s).abs() < 0.015, "{integrated:?} vs {sampled:?}");
+        }
+    }
+
+    /// Views from above, from high up to grazing.
+    fn views() -> [Vec3; 3] {
+        [
+            Vec3::new(0.1, 0.2, 1.).unit_vector(),
+            Vec3::new(-0.7, 0.2, 0.6).unit_vector(),
+            Vec3::new(0.4, -0.9, 0.1).unit_vector(),
+        ]
+    }
+
+    #[test]
+    fn diffuse_samples_match() {
+        let rng = Rng::with_seed(1);
+        let reflectance = Color::new(0.9, 0.5, 0.1);
+
+        for sigma in [0., 20f32.to_radians(), 60f32.to_radians()] {
+            for wo in views() {
+                assert_samples_match(DiffuseBxdf::oren_nayar(reflectance, sigma), wo, &rng);
+            }
+        }
+    }
+
+    #[test]
+    fn oren_nayar() {
+        let white = Color::new(1., 1., 1.);
+        let lambertian = DiffuseBxdf::new(white);
+        let rough = DiffuseBxdf::oren_nayar(white, 30f32.to_radians());
+        let wo = Vec3::new(0.6, 0., 0.8);
+        let mirrored = Vec3::new(-0.6, 0., 0.8);
+
+        // Without grooves it's Lambertian, and with them, light goes back where it came from
+        // more than it goes on.
+        assert_eq!(DiffuseBxdf::oren_nayar(white, 0.), lambertian);
+        assert_eq!(lambertian.eval(wo, wo), lambertian.eval(wo, mirrored));
+        assert!(rough.eval(wo, wo).x() > 1.1 * rough.eval(wo, mirrored).x());
+
+        // Seen from straight above, the surface is a bit darker.
+        let above = Vec3::new(0., 0., 1.);
+        assert!(rough.eval(above, wo).x() < lambertian.eval(above, wo).x());
+    }
+
+    #[test]
+    fn conductor_samples_match() {
+        let rng = Rng::with_seed(2);
+        let copper = Fresnel::Conductor {
+            eta: Color::new(0.2, 0.924, 1.102),
+            k: Color::new(3.912, 2.452, 2.142),
+        };
+
+        for roughness in [Ggx::from_roughness(0.5, 0.5), Ggx::from_roughness(0.2, 0.7)] {
+            for wo in views() {
+                assert_samples_match(ConductorBxdf::new(copper, roughness), wo, &rng);
+            }
+        }
+    }
+
+    #[test]
+    fn dielectric_samples_match() {
+        let rng = Rng::with_seed(3);
+        let tint = Color::new(1., 0.8, 0.6);
+
+        for roughness in [Ggx::from_roughness(0.5, 0.5), Ggx::from_roughness(0.4, 0.7)] {
+            // Going into the glass, and out of it.
+            for eta in [1.5, 1. / 1.5] {
+                for wo in views() {
+                    let glass = DielectricBxdf::new(eta, roughness, tint, Color::new(1., 1., 1.));
+                    assert_samples_match(glass, wo, &rng);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dielectric_smooth_along_one_axis() {
+        let rng = Rng::with_seed(6);
+        let white = Color::new(1., 1., 1.);
+
+        for eta in [1.5, 1. / 1.5] {
+            let glass = DielectricBxdf::new(eta, Ggx::from_roughness(0., 0.5), white, white);
+
+            for _ in 0..10_000 {
+                let wo = cosine_hemisphere((rng.f32(), rng.f32()));
+                let wi = uniform_sphere((rng.f32(), rng.f32()));
+                let f = glass.eval(wo, wi);
+                let pdf = glass.pdf(wo, wi);
+                assert!(f.x().is_finite() && pdf.is_finite(), "{wo:?} {wi:?}");
+
+                if let Some(sample) = glass.sample(wo, rng.f32(), (rng.f32(), rng.f32())) {
+                    let w = sample.weight();
+                    assert!(w.x().is_finite() && sample.pdf.is_finite(), "{sample:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn principled_samples_match() {
+        let rng = Rng::with_seed(4);
+        let roughness = Ggx::from_roughness(0.4, 0.6);
+        let white = Color::new(1., 1., 1.);
+        let base = Color::new(0.8, 0.5, 0.2);
+        let specular =
+            ConductorBxdf::new(Fresnel::Schlick(Color::new(0.04, 0.05, 0.06)), roughness);
+        let glass = DielectricBxdf::new(1.5, roughness, base, white);
+
+        for (sheen, clearcoat, weights) in [
+            (
+                Color::new(1., 0.9, 0.8),
+                Gtr1::new(0.05),
+                [1., 1., 0., 0.25],
+            ),
+            (Color::default(), Gtr1::new(0.1), [0.3, 1., 0., 0.]),
+            (Color::default(), Gtr1::new(0.1), [0.2, 0.2, 0.8, 0.1]),
+        ] {
+            for wo in views() {
+                let principled =
+                    PrincipledBxdf::new(base, 0.5, sheen, specular, glass, clearcoat, weights);
+                assert_samples_match(principled, wo, &rng);
+            }
+        }
+    }
+
+    #[test]
+    fn henyey_greenstein_samples_match() {
+        let rng = Rng::with_seed(5);
+        let wo = Vec3::new(0.3, -1., 0.2).unit_vector();
+
+        for g in [0., 0.6, -0.4] {
+            let phase = HenyeyGreenstein::new(Color::new(0.5, 0.5, 0.5), g);
+            assert_samples_match(phase, wo, &rng);
+
+            // Sampled directions average to a cosine of g with the ray.
+            let n = 100_000;
+            let mean_cosine = (0..n)
+                .map(|_| {
+                    let sample = phase.sample(wo, rng.f32(), (rng.f32(), rng.f32())).unwrap();
+                    (-wo).dot(sample.wi)
+                })
+                .sum::<f32>()
+                / n as f32;
+            assert!((mean_cosine - g).abs() < 0.01, "{g}: {mean_cosine}");
+        }
+    }
+
+    #[test]
+    fn specular_samples() {
+        let wo = Vec3::new(0.6, 0., 0.8);
+        let mirror = ConductorBxdf::new(
+            Fresnel::Schlick(Color::new(0.9, 0.9, 0.9)),
+            Ggx::from_roughness(0., 0.),
+        );
+        let sample = mirror.sample(wo, 0.5, (0.5, 0.5)).unwrap();
+        assert_eq!(sample.wi, Vec3::new(-0.6, 0., 0.8));
+        assert!(sample.flags.contains(Flags::REFLECTION | Flags::SPECULAR));
+        assert_eq!(
+            (mirror.eval(wo, sample.wi), mirror.pdf(wo, sample.wi)),
+            (Color::default(), 0.)
+        );
+
+        // Smooth glass reflects and refracts with the odds of the Fresnel reflectance, and
+        // weighs them alike.
+        let glass = DielectricBxdf::new(
+            1.5,
+            Ggx::from_roughness(0., 0.),
+            Color::new(1., 1., 1.),
+            Color::new(1., 1., 1.),
+        );
+        let fresnel = crate::microfacet::fresnel_dielectric(wo.z(), 1.5);
+        let reflected = glass.sample(wo, fresnel * 0.99, (0.5, 0.5)).unwrap();
+        let refracted = glass.sample(wo, fresnel * 1.01, (0.5, 0.5)).unwrap();
+        assert_eq!(reflected.wi, Vec3::new(-0.6, 0., 0.8));
+        assert!(refracted.flags.contains(Flags::TRANSMISSION));
+        assert!((refracted.wi.x() + 0.4).abs() < 1e-6 && refracted.wi.z() < 0.);
+        assert!((reflected.pdf - fresnel).abs() < 1e-6);
+        assert_eq!(reflected.weight(), refracted.weight());
+    }
+}
@@ -0,0 +1,410 @@
+//! Bounding volume hierarchy, built with the surface area heuristic (SAH) and flattened into a
+//! depth-first array of nodes so traversal walks contiguous memory.
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    ray::{Point3, Ray},
+    vec3::Vec3,
+};
+
+use std::fmt;
+
+use fastrand::Rng;
+
+/// Number of buckets centroids are binned into when evaluating split candidates.
+const BINS: usize = 16;
+/// Nodes with at most this many primitives become leaves if splitting doesn't pay off.
+const MAX_LEAF_SIZE: usize = 4;
+/// Cost of visiting a node relative to intersecting one primitive.
+const TRAVERSAL_COST: f32 = 0.125;
+/// Size of the traversal stack, the build stops splitting before getting this deep.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    bounds: Aabb,
+    /// Leaf: index of the first primitive. Interior: index of the second child, the first one
+    /// is always the next node.
+    offset: u32,
+    /// Primitives in a leaf, 0 for interior nodes.
+    count: u32,
+    /// Axis the children were split along.
+    axis: u8,
+}
+
+struct BuildItem {
+    bounds: Aabb,
+    centroid: Point3,
+    index: usize,
+}
+
+#[derive(Clone)]
+pub struct Bvh<T> {
+    nodes: Vec<Node>,
+    primitives: Vec<T>,
+    /// Primitives without a bounding box, like infinite planes, tested against every ray.
+    unbounded: Vec<T>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BvhStats {
+    pub primitives: usize,
+    pub nodes: usize,
+    pub leaves: usize,
+    pub depth: usize,
+    /// Expected cost of a random ray, in primitive intersections.
+    pub sah_cost: f32,
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "BVH: {} primitives, {} nodes ({} leaves), depth {}, SAH cost {:.2}",
+            self.primitives, self.nodes, self.leaves, self.depth, self.sah_cost
+        )
+    }
+}
+
+impl<T: Hittable> Bvh<T> {
+    pub fn new(objects: Vec<T>) -> Self {
+        let mut items = Vec::with_capacity(objects.len());
+        let mut slots = Vec::with_capacity(objects.len());
+        let mut unbounded = Vec::new();
+
+        for o in objects {
+            match o.bounding_box() {
+                Some(bounds) => {
+                    items.push(BuildItem {
+                        bounds,
+                        centroid: bounds.centroid(),
+                        index: slots.len(),
+                    });
+                    slots.push(Some(o));
+                }
+                None => unbounded.push(o),
+            }
+        }
+
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * items.len()),
+            primitives: Vec::with_capacity(items.len()),
+            unbounded,
+        };
+
+        if !items.is_empty() {
+            let mut order = Vec::with_capacity(items.len());
+            bvh.build(&mut items, &mut order, 0);
+
+            bvh.primitives = order
+                .into_iter()
+                .map(|i| slots[i].take().unwrap())
+                .collect();
+        }
+
+        bvh
+    }
+
+    /// Appends the subtree for `items` and returns the index of its root.
+    fn build(&mut self, items: &mut [BuildItem], order: &mut Vec<usize>, depth: usize) -> usize {
+        let bounds = items
+            .iter()
+            .fold(Aabb::EMPTY, |b, item| b.union(&item.bounds));
+
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            bounds,
+            offset: 0,
+            count: 0,
+            axis: 0,
+        });
+
+        let centroid_bounds = items
+            .iter()
+            .fold(Aabb::EMPTY, |b, item| b.grow(item.centroid));
+        let axis = centroid_bounds.largest_axis();
+        let min = centroid_bounds.min[axis];
+        let extent = centroid_bounds.extent()[axis];
+
+        let split = if items.len() == 1 || extent <= 0. || depth + 1 >= MAX_DEPTH {
+            None
+        } else {
+            let bin_of =
+                |c: Point3| (((c[axis] - min) / extent * BINS as f32) as usize).min(BINS - 1);
+
+            let mut bins = [(0usize, Aabb::EMPTY); BINS];
+            for item in items.iter() {
+                let bin = &mut bins[bin_of(item.centroid)];
+                bin.0 += 1;
+                bin.1 = bin.1.union(&item.bounds);
+            }
+
+            // Cost of splitting after each bin, sweeping from both sides.
+            let mut costs = [0.; BINS - 1];
+            let (mut count, mut acc) = (0, Aabb::EMPTY);
+            for (cost, bin) in costs.iter_mut().zip(&bins) {
+                count += bin.0;
+                acc = acc.union(&bin.1);
+                *cost = count as f32 * acc.surface_area();
+            }
+            let (mut count, mut acc) = (0, Aabb::EMPTY);
+            for (cost, bin) in costs.iter_mut().zip(&bins[1..]).rev() {
+                count += bin.0;
+                acc = acc.union(&bin.1);
+                *cost += count as f32 * acc.surface_area();
+            }
+
+            let (best, cost) = costs
+                .iter()
+                .enumerate()
+                .map(|(i, c)| (i, TRAVERSAL_COST + c / bounds.surface_area()))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap();
+
+            if items.len() > MAX_LEAF_SIZE || cost < items.len() as f32 {
+                Some(partition(items, |item| bin_of(item.centroid) <= best))
+            } else {
+                None
+            }
+        };
+
+        match split {
+            Some(mid) if mid > 0 && mid < items.len() => {
+                let (left, right) = items.split_at_mut(mid);
+                self.build(left, order, depth + 1);
+                let second = self.build(right, order, depth + 1);
+
+                let node = &mut self.nodes[index];
+                node.offset = second as u32;
+                node.axis = axis as u8;
+            }
+            _ => {
+                let node = &mut self.nodes[index];
+                node.offset = order.len() as u32;
+                node.count = items.len() as u32;
+                order.extend(items.iter().map(|item| item.index));
+            }
+        }
+
+        index
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        let mut stats = BvhStats {
+            primitives: self.primitives.len() + self.unbounded.len(),
+            nodes: self.nodes.len(),
+            leaves: 0,
+            depth: 0,
+            sah_cost: self.unbounded.len() as f32,
+        };
+
+        if self.nodes.is_empty() {
+            return stats;
+        }
+
+        let root_area = self.nodes[0].bounds.surface_area();
+        let mut stack = vec![(0, 1)];
+
+        while let Some((i, depth)) = stack.pop() {
+            let node = &self.nodes[i];
+            let p = if root_area > 0. {
+                node.bounds.surface_area() / root_area
+            } else {
+                1.
+            };
+            stats.depth = stats.depth.max(depth);
+
+            if node.count > 0 {
+                stats.leaves += 1;
+                stats.sah_cost += p * node.count as f32;
+            } else {
+                stats.sah_cost += p * TRAVERSAL_COST;
+                stack.push((i + 1, depth + 1));
+                stack.push((node.offset as usize, depth + 1));
+            }
+        }
+
+        stats
+    }
+}
+
+/// Moves the items satisfying `pred` to the front and returns how many there are.
+fn partition<T>(items: &mut [T], pred: impl Fn(&T) -> bool) -> usize {
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if pred(&items[i]) {
+            items.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+impl<T: Hittable> Bvh<T> {
+    /// Closest surface hit, along with the primitive that was hit.
+    pub fn closest_surface(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<(HitRecord<'_>, &T)> {
+        self.closest(r, t_min, t_max, T::hit_surface)
+    }
+
+    /// Closest hit found by `hit`, which is [`Hittable::hit`] or [`Hittable::hit_surface`].
+    fn closest<'a>(
+        &'a self,
+        r: &Ray,
+        t_min: f32,
+        t_max: f32,
+        hit: impl Fn(&'a T, &Ray, f32, f32) -> Option<HitRecord<'a>>,
+    ) -> Option<(HitRecord<'a>, &'a T)> {
+        let mut hit_record = None;
+
+        self.traverse(r, t_min, t_max, |o, closest_so_far| {
+            match hit(o, r, t_min, closest_so_far) {
+                Some(hit) => {
+                    let t = hit.t;
+                    hit_record = Some((hit, o));
+                    t
+                }
+                None => closest_so_far,
+            }
+        });
+
+        hit_record
+    }
+
+    /// Calls `visit` on the unbounded primitives and on those in the leaves the ray reaches
+    /// between `t_min` and `t_max`, nearest nodes first. `visit` gets how far along the ray
+    /// primitives still matter and returns the new limit, which prunes the nodes beyond it.
+    fn traverse<'a>(
+        &'a self,
+        r: &Ray,
+        t_min: f32,
+        mut t_max: f32,
+        mut visit: impl FnMut(&'a T, f32) -> f32,
+    ) {
+        for o in &self.unbounded {
+            t_max = visit(o, t_max);
+        }
+
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let d = r.direction;
+        let inv_direction = Vec3::new(1. / d.x(), 1. / d.y(), 1. / d.z());
+        let direction_is_negative = [d.x() < 0., d.y() < 0., d.z() < 0.];
+
+        let mut stack = [0u32; MAX_DEPTH];
+        let mut stack_len = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+
+            if node.bounds.hit(r.origin, inv_direction, t_min, t_max) {
+                if node.count > 0 {
+                    let start = node.offset as usize;
+                    for o in &self.primitives[start..start + node.count as usize] {
+                        t_max = visit(o, t_max);
+                    }
+                } else {
+                    // Visit the child closer to the ray origin first.
+                    let (near, far) = if direction_is_negative[node.axis as usize] {
+                        (node.offset, current as u32 + 1)
+                    } else {
+                        (current as u32 + 1, node.offset)
+                    };
+
+                    stack[stack_len] = far;
+                    stack_len += 1;
+                    current = near as usize;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            current = stack[stack_len] as usize;
+        }
+    }
+}
+
+impl<T: Hittable> Hittable for Bvh<T> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &Rng) -> Option<HitRecord<'_>> {
+        self.closest(r, t_min, t_max, |o, r, t_min, t_max| {
+            o.hit(r, t_min, t_max, rng)
+        })
+        .map(|(hit, _)| hit)
+    }
+
+    fn hit_surface(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.closest_surface(r, t_min, t_max).map(|(hit, _)| hit)
+    }
+
+    /// Once no light gets through, the limit drops below the ray and the traversal ends.
+    fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32, rng: &Rng) -> f32 {
+        let mut transmittance = 1.;
+
+        self.traverse(r, t_min, t_max, |o, t_max| {
+            transmittance *= o.transmittance(r, t_min, t_max, rng);
+            if transmittance > 0. {
+                t_max
+            } else {
+                f32::NEG_INFINITY
+            }
+        });
+
+        transmittance
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+
+        self.nodes.first().map(|n| n.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Bvh;
+    use crate::{
+        hittable::{Hittable, HittableList},
+        material::{Lambertian, Materials},
+        ray::Ray,
+        sphere::Sphere,
+        vec3::Vec3,
+    };
+
+    #[test]
+    fn matches_linear_list() {
+        let rng = fastrand::Rng::with_seed(7);
+        let random =
+            |scale: f32| Vec3::new(rng.f32() - 0.5, rng.f32() - 0.5, rng.f32() - 0.5) * scale;
+
+        let material = Materials::Lambertian(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let spheres: Vec<_> = (0..500)
+            .map(|_| Sphere::new(random(20.), 0.1 + rng.f32(), material.clone()))
+            .collect();
+
+        let mut list = HittableList::default();
+        for s in &spheres {
+            list.add(s.clone());
+        }
+        let bvh = Bvh::new(spheres);
+
+        let stats = bvh.stats();
+        assert_eq!(stats.primitives, 500);
+        assert!(stats.sah_cost < 500.);
+
+        for _ in 0..2000 {
+            let r = Ray::new(random(30.), random(1.));
+            let expected = list.hit_surface(&r, 0.001, f32::INFINITY).map(|h| h.t);
+            let actual = bvh.hit_surface(&r, 0.001, f32::INFINITY).map(|h| h.t);
+
+            assert_eq!(expected, actual);
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     ray::{Point3, Ray},
+    sampler,
     vec3::Vec3,
-    ASPECT_RATIO,
 };
 
 pub struct Camera {
@@ -13,6 +13,8 @@ pub struct Camera {
     v: Vec3,
     //w: Vec3,
     lens_radius: f32,
+    /// Times the shutter opens and closes at.
+    shutter: (f32, f32),
 }
 
 impl Camera {
@@ -22,13 +24,14 @@ impl Camera {
         lookat: Point3,
         vup: Vec3,
         vfov: f32,
+        aspect_ratio: f32,
         aperture: f32,
         focus_dist: f32,
     ) -> Self {
         let theta = vfov.to_radians();
         let h = (theta / 2.).tan();
         let viewport_height = 2.0 * h;
-        let viewport_width = ASPECT_RATIO * viewport_height;
+        let viewport_width = aspect_ratio * viewport_height;
 
         let w = (lookfrom - lookat).unit_vector();
         let u = vup.cross(w).unit_vector();
@@ -49,15 +52,31 @@ impl Camera {
             v,
             //    w,
             lens_radius,
+            shutter: (0., 1.),
         }
     }
-    pub fn ger_ray(&self, s: f32, t: f32) -> Ray {
-        let rd = self.lens_radius * Vec3::random_in_unit_disk();
+
+    /// Rays are shot at uniformly random times between `open` and `close`, 0 and 1 by default.
+    /// Moving objects go from their start to their end position between times 0 and 1.
+    pub fn with_shutter(self, open: f32, close: f32) -> Self {
+        Self {
+            shutter: (open, close),
+            ..self
+        }
+    }
+
+    /// Ray through `s`, `t` on the viewport, from the point `lens` picks on the lens at the time
+    /// `time` picks during the exposure.
+    pub fn ger_ray(&self, s: f32, t: f32, lens: (f32, f32), time: f32) -> Ray {
+        let rd = self.lens_radius * sampler::uniform_disk(lens);
         let offset = self.u * rd.x() + self.v * rd.y();
 
-        Ray::new(
+        let (open, close) = self.shutter;
+
+        Ray::with_time(
             self.origin + offset,
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
+            open + time * (close - open),
         )
     }
 }
@@ -0,0 +1,317 @@
+//! Command-line interface.
+
+use crate::{integrator::Integrator, output::Format, sampler::Sampling};
+
+use std::{fmt, path::PathBuf};
+
+pub const USAGE: &str = "\
+Usage: rayo render [SCENE] [OPTIONS]
+
+Renders SCENE, a scene description file, or the random scene when omitted.
+
+Options:
+  --width <PIXELS>        Image width [default: 400]
+  --aspect <W:H>          Aspect ratio, as W:H or a number [default: 16:9]
+  --spp <N>               Samples per pixel, the most any pixel takes with
+                          --adaptive [default: 10]
+  --adaptive <ERROR>      Stop sampling a pixel once the standard error of its
+                          brightness is below ERROR times the brightness,
+                          e.g. 0.02 [default: off]
+  --min-spp <N>           Samples between checks of the error with --adaptive
+                          [default: 16]
+  --spp-map <PATH>        Also write the samples each pixel took, as a fraction
+                          of --spp
+  --max-depth <N>         Maximum ray bounces [default: 1000]
+  --rr-depth <N>          Bounces before Russian roulette can end a path
+                          [default: 3]
+  --integrator <NAME>     path (follows scattered rays only) or nee (also samples
+                          the lights) [default: nee]
+  --sampler <NAME>        independent, stratified, halton or sobol (Owen
+                          scrambled, best with powers of two samples)
+                          [default: sobol]
+  -o, --output <PATH>     Output image [default: image.ppm]
+  --format <FORMAT>       ppm (binary), ppm-ascii, png, hdr, pfm or exr
+                          [default: from the output extension]
+  --seed <N>              Seed of the random numbers, the same seed renders the
+                          same image on any number of threads [default: 0]
+  --threads <N>           Worker threads [default: one per core]
+  --bvh-stats             Print statistics of the scene's BVH
+  -h, --help              Print this help";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub scene: Option<PathBuf>,
+    pub width: u32,
+    pub aspect_ratio: f32,
+    pub samples_per_pixel: u32,
+    /// Error a pixel stops sampling at, see
+    /// [`PixelEstimate::relative_error`](crate::adaptive::PixelEstimate::relative_error).
+    pub adaptive: Option<f32>,
+    pub min_samples_per_pixel: u32,
+    pub max_depth: u32,
+    pub roulette_depth: u32,
+    pub integrator: Integrator,
+    pub sampler: Sampling,
+    pub output: PathBuf,
+    pub format: Format,
+    pub spp_map: Option<(PathBuf, Format)>,
+    pub seed: u64,
+    pub threads: Option<usize>,
+    pub bvh_stats: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            scene: None,
+            width: 400,
+            aspect_ratio: 16. / 9.,
+            samples_per_pixel: 10,
+            adaptive: None,
+            min_samples_per_pixel: 16,
+            max_depth: 1000,
+            roulette_depth: 3,
+            integrator: Integrator::Nee,
+            sampler: Sampling::Sobol,
+            output: PathBuf::from("image.ppm"),
+            format: Format::Ppm,
+            spp_map: None,
+            seed: 0,
+            threads: None,
+            bvh_stats: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn height(&self) -> u32 {
+        ((self.width as f32 / self.aspect_ratio).round() as u32).max(1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Render(Settings),
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CliError(String);
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// Parses the arguments, without the program name.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, CliError> {
+    let mut args = args.into_iter();
+
+    match args.next().as_deref() {
+        Some("render") => {}
+        Some("-h" | "--help" | "help") => return Ok(Command::Help),
+        Some(cmd) => return Err(CliError(format!("unknown command `{cmd}`"))),
+        None => return Err(CliError("missing command".into())),
+    }
+
+    let mut settings = Settings::default();
+    let mut format = None;
+    let mut spp_map = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| CliError(format!("missing value for `{arg}`")))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--width" => settings.width = number(&arg, &value()?)?,
+            "--aspect" => settings.aspect_ratio = aspect_ratio(&value()?)?,
+            "--spp" => settings.samples_per_pixel = number(&arg, &value()?)?,
+            "--adaptive" => {
+                let error: f32 = number(&arg, &value()?)?;
+                if !(error.is_finite() && error > 0.) {
+                    return Err(CliError(format!("invalid value `{error}` for `{arg}`")));
+                }
+                settings.adaptive = Some(error);
+            }
+            "--min-spp" => settings.min_samples_per_pixel = number(&arg, &value()?)?,
+            "--max-depth" => settings.max_depth = number(&arg, &value()?)?,
+            "--rr-depth" => settings.roulette_depth = number(&arg, &value()?)?,
+            "--integrator" => {
+                let name = value()?;
+                settings.integrator = Integrator::from_name(&name).ok_or_else(|| {
+                    CliError(format!(
+                        "unknown integrator `{name}`, expected {}",
+                        Integrator::NAMES
+                    ))
+                })?;
+            }
+            "--sampler" => {
+                let name = value()?;
+                settings.sampler = Sampling::from_name(&name).ok_or_else(|| {
+                    CliError(format!(
+                        "unknown sampler `{name}`, expected {}",
+                        Sampling::NAMES
+                    ))
+                })?;
+            }
+            "-o" | "--output" => settings.output = PathBuf::from(value()?),
+            "--format" => {
+                let name = value()?;
+                format = Some(Format::from_name(&name).ok_or_else(|| {
+                    CliError(format!(
+                        "unknown format `{name}`, expected {}",
+                        Format::NAMES
+                    ))
+                })?);
+            }
+            "--spp-map" => spp_map = Some(PathBuf::from(value()?)),
+            "--seed" => settings.seed = number(&arg, &value()?)?,
+            "--threads" => settings.threads = Some(number(&arg, &value()?)?),
+            "--bvh-stats" => settings.bvh_stats = true,
+            _ if arg.starts_with('-') => return Err(CliError(format!("unknown option `{arg}`"))),
+            _ if settings.scene.is_none() => settings.scene = Some(PathBuf::from(arg)),
+            _ => return Err(CliError(format!("unexpected argument `{arg}`"))),
+        }
+    }
+
+    if settings.width == 0
+        || settings.samples_per_pixel == 0
+        || settings.min_samples_per_pixel == 0
+        || settings.threads == Some(0)
+    {
+        return Err(CliError(
+            "`--width`, `--spp`, `--min-spp` and `--threads` must be greater than zero".into(),
+        ));
+    }
+
+    settings.format = format
+        .or_else(|| Format::from_path(&settings.output))
+        .ok_or_else(|| {
+            CliError(format!(
+                "can't tell the image format of `{}`, use `--format`",
+                settings.output.display()
+            ))
+        })?;
+
+    settings.spp_map = spp_map
+        .map(|path: PathBuf| match Format::from_path(&path) {
+            Some(format) => Ok((path, format)),
+            None => Err(CliError(format!(
+                "can't tell the image format of `{}`",
+                path.display()
+            ))),
+        })
+        .transpose()?;
+
+    Ok(Command::Render(settings))
+}
+
+fn number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, CliError> {
+    value
+        .parse()
+        .map_err(|_| CliError(format!("invalid value `{value}` for `{option}`")))
+}
+
+/// Either `W:H` or a plain ratio like `1.5`.
+fn aspect_ratio(value: &str) -> Result<f32, CliError> {
+    let ratio = match value.split_once(':') {
+        Some((w, h)) => number::<f32>("--aspect", w)? / number::<f32>("--aspect", h)?,
+        None => number("--aspect", value)?,
+    };
+
+    if ratio.is_finite() && ratio > 0. {
+        Ok(ratio)
+    } else {
+        Err(CliError(format!("invalid value `{value}` for `--aspect`")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Command, Settings};
+    use crate::{integrator::Integrator, output::Format, sampler::Sampling};
+    use std::path::PathBuf;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn full_command_line() {
+        let cmd = parse(args(
+            "render scene.toml --width 1920 --aspect 16:9 --spp 512 --adaptive 0.05 --min-spp 32 --max-depth 64 --rr-depth 5 --integrator path --sampler halton -o out.png --spp-map spp.exr --seed 42 --threads 8",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            cmd,
+            Command::Render(Settings {
+                scene: Some(PathBuf::from("scene.toml")),
+                width: 1920,
+                aspect_ratio: 16. / 9.,
+                samples_per_pixel: 512,
+                adaptive: Some(0.05),
+                min_samples_per_pixel: 32,
+                max_depth: 64,
+                roulette_depth: 5,
+                integrator: Integrator::Path,
+                sampler: Sampling::Halton,
+                output: PathBuf::from("out.png"),
+                format: Format::Png,
+                spp_map: Some((PathBuf::from("spp.exr"), Format::Exr)),
+                seed: 42,
+                threads: Some(8),
+                ..Settings::default()
+            })
+        );
+
+        if let Command::Render(settings) = cmd {
+            assert_eq!(settings.height(), 1080);
+        }
+    }
+
+    #[test]
+    fn output_format() {
+        let format = |s| match parse(args(s)) {
+            Ok(Command::Render(settings)) => settings.format,
+            _ => panic!("invalid arguments"),
+        };
+
+        assert_eq!(format("render"), Format::Ppm);
+        assert_eq!(format("render -o a.PNG"), Format::Png);
+        assert_eq!(
+            format("render -o a.png --format ppm-ascii"),
+            Format::PpmAscii
+        );
+        assert_eq!(format("render -o a.img --format png"), Format::Png);
+        assert_eq!(format("render -o a.exr"), Format::Exr);
+        assert_eq!(format("render -o a.hdr"), Format::Hdr);
+        assert_eq!(format("render -o a.pfm"), Format::Pfm);
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(parse(args("render --width")).is_err());
+        assert!(parse(args("render --width -3")).is_err());
+        assert!(parse(args("render --max-depth -1")).is_err());
+        assert!(parse(args("render --rr-depth many")).is_err());
+        assert!(parse(args("render --seed -1")).is_err());
+        assert!(parse(args("render --adaptive 0")).is_err());
+        assert!(parse(args("render --min-spp 0")).is_err());
+        assert!(parse(args("render --spp-map samples")).is_err());
+        assert!(parse(args("render --aspect 16:0")).is_err());
+        assert!(parse(args("render a.toml b.toml")).is_err());
+        assert!(parse(args("draw")).is_err());
+        assert!(parse(args("render -o image.tga")).is_err());
+        assert!(parse(args("render --format gif")).is_err());
+        assert!(parse(args("render --integrator bdpt")).is_err());
+        assert!(parse(args("render --sampler pmj")).is_err());
+        assert_eq!(parse(args("render --help")), Ok(Command::Help));
+    }
+}
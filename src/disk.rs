@@ -0,0 +1,207 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Materials,
+    onb::Onb,
+    ray::{Point3, Ray},
+    vec3::Vec3,
+};
+
+use std::f32::consts::PI;
+
+/// Flat disk, with its front face towards `normal`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disk {
+    center: Point3,
+    radius: f32,
+    /// `w` is the unit normal, `u` and `v` span the disk.
+    frame: Onb,
+    material: Materials,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f32, m: Materials) -> Self {
+        Self {
+            center,
+            radius: radius.abs(),
+            frame: Onb::from_w(normal),
+            material: m,
+        }
+    }
+
+    pub fn material(&self) -> &Materials {
+        &self.material
+    }
+
+    fn area(&self) -> f32 {
+        PI * self.radius * self.radius
+    }
+}
+
+impl Hittable for Disk {
+    /// `u` of the hit is the angle around the center, from 0 to 1, and `v` its distance from the
+    /// center, from 0 to 1 on the rim.
+    fn hit_surface(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let denom = self.frame.w.dot(r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = self.frame.w.dot(self.center - r.origin) / denom;
+        if !(t_min..=t_max).contains(&t) {
+            return None;
+        }
+
+        let point = r.at(t);
+        let planar = point - self.center;
+        let (x, y) = (planar.dot(self.frame.u), planar.dot(self.frame.v));
+        let rho = (x * x + y * y).sqrt();
+        if rho > self.radius {
+            return None;
+        }
+
+        let (u, v) = (self.frame.u, self.frame.v);
+        let dpdu = (v * x - u * y) * (2. * PI);
+        let dpdv = if rho > 1e-6 {
+            (u * x + v * y) * (self.radius / rho)
+        } else {
+            // At the center the angle is degenerate, any tangent will do.
+            u * self.radius
+        };
+
+        let mut hr = HitRecord::new(point, self.frame.w, t, &self.material);
+        hr.set_face_normal(r);
+        hr.u = y.atan2(x).rem_euclid(2. * PI) / (2. * PI);
+        hr.v = rho / self.radius;
+        (hr.dpdu, hr.dpdv) = (dpdu, dpdv);
+
+        Some(hr)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let n = self.frame.w;
+        let extent = |c: f32| self.radius * (1. - c * c).max(0.).sqrt();
+        let e = Vec3::new(extent(n.x()), extent(n.y()), extent(n.z()));
+
+        Some(Aabb::new(self.center - e, self.center + e).pad(1e-4))
+    }
+
+    /// Points are sampled uniformly by area.
+    fn pdf_value(&self, origin: Point3, direction: Vec3, _time: f32) -> f32 {
+        let Some(hit) = self.hit_surface(&Ray::new(origin, direction), 0.001, f32::INFINITY) else {
+            return 0.;
+        };
+
+        let distance_squared = hit.t * hit.t * direction.len_squared();
+        let cosine = self.frame.w.dot(direction).abs() / direction.len();
+
+        distance_squared / (cosine * self.area())
+    }
+
+    fn random(&self, origin: Point3, _time: f32, u: (f32, f32)) -> Vec3 {
+        let r = self.radius * u.0.sqrt();
+        let phi = 2. * PI * u.1;
+
+        let offset = self
+            .frame
+            .local(Vec3::new(r * phi.cos(), r * phi.sin(), 0.));
+
+        self.center + offset - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Disk;
+    use crate::{
+        hittable::Hittable,
+        material::{Lambertian, Materials},
+        ray::{Point3, Ray},
+        sampler::uniform_sphere,
+        vec3::Vec3,
+    };
+    use std::f32::consts::PI;
+
+    use fastrand::Rng;
+
+    #[test]
+    fn polar_mapping_and_bounds() {
+        let center = Point3::new(1., 2., 3.);
+        let disk = Disk::new(
+            center,
+            Vec3::new(1., 1., 0.),
+            2.,
+            Materials::Lambertian(Lambertian::default()),
+        );
+        let bounds = disk.bounding_box().unwrap();
+
+        for target in [Vec3::new(0.5, -0.5, 1.), Vec3::new(-1., 1., -0.7)] {
+            let origin = center + Vec3::new(3., 3., 0.5);
+            let hit = disk
+                .hit_surface(
+                    &Ray::new(origin, center + target - origin),
+                    0.001,
+                    f32::INFINITY,
+                )
+                .unwrap();
+            assert!(hit.front_face);
+            assert!((hit.point - (center + target)).len() < 1e-4);
+            assert!((hit.v - target.len() / 2.).abs() < 1e-5);
+
+            // Moving along the derivatives stays on the disk, at the expected coordinates.
+            let h = 1e-3;
+            let moved = disk
+                .hit_surface(
+                    &Ray::new(origin, hit.point + hit.dpdu * h + hit.dpdv * h - origin),
+                    0.001,
+                    f32::INFINITY,
+                )
+                .unwrap();
+            assert!((moved.u - hit.u - h).abs() < 1e-4 && (moved.v - hit.v - h).abs() < 1e-4);
+
+            let rim = center + target.unit_vector() * 2.;
+            assert!(rim.min(bounds.min) == bounds.min && rim.max(bounds.max) == bounds.max);
+        }
+    }
+
+    #[test]
+    fn tangent_at_the_center() {
+        let disk = Disk::new(
+            Point3::new(0., 0., 0.),
+            Vec3::new(0., 0., 1.),
+            2.,
+            Materials::Lambertian(Lambertian::default()),
+        );
+
+        // The angle around the center is degenerate, but the shading frame still lines up
+        // with the radius.
+        let r = Ray::new(Point3::new(0., 0., 3.), Vec3::new(0., 0., -1.));
+        let hit = disk.hit_surface(&r, 0.001, f32::INFINITY).unwrap();
+        assert_eq!(hit.dpdu, Vec3::default());
+        assert!((hit.tangent().cross(hit.dpdv) - hit.normal * 4.).len() < 1e-5);
+    }
+
+    #[test]
+    fn light_pdf_integrates_to_one() {
+        let rng = Rng::with_seed(1);
+        let disk = Disk::new(
+            Point3::new(0., 0., -1.),
+            Vec3::new(0., 0.5, 1.),
+            0.8,
+            Materials::Lambertian(Lambertian::default()),
+        );
+        let origin = Point3::new(0.2, -0.1, 0.);
+
+        let n = 200_000;
+        let integral = (0..n)
+            .map(|_| disk.pdf_value(origin, uniform_sphere((rng.f32(), rng.f32())), 0.))
+            .sum::<f32>()
+            * 4.
+            * PI
+            / n as f32;
+        assert!((integral - 1.).abs() < 0.03, "{integral}");
+
+        let direction = disk.random(origin, 0., (rng.f32(), rng.f32()));
+        assert!(disk.pdf_value(origin, direction, 0.) > 0.);
+    }
+}
@@ -0,0 +1,176 @@
+//! Writers for high dynamic range formats, which store the linear framebuffer unclamped.
+
+use crate::vec3::Color;
+
+use std::io::{self, Write};
+
+/// Radiance `.hdr`: shared-exponent RGBE pixels in flat (not run-length encoded) scanlines.
+pub fn write_radiance(
+    w: &mut impl Write,
+    width: u32,
+    height: u32,
+    pixels: &[Color],
+) -> io::Result<()> {
+    write!(
+        w,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {height} +X {width}\n"
+    )?;
+
+    let data: Vec<u8> = pixels.iter().flat_map(|&p| to_rgbe(p)).collect();
+    w.write_all(&data)
+}
+
+/// Shares the exponent of the largest component: each mantissa is `c / 2^(e - 128) * 256`.
+pub fn to_rgbe(c: Color) -> [u8; 4] {
+    let c = c.max(Color::default());
+    let v = c.x().max(c.y()).max(c.z());
+
+    if !v.is_finite() || v < 1e-32 {
+        return [0; 4];
+    }
+
+    // v = m * 2^e with m in [0.5, 1), read straight from the bits since v is normal.
+    let e = ((v.to_bits() >> 23) & 0xff) as i32 - 126;
+    let scale = ((8 - e) as f32).exp2();
+
+    [
+        (c.x() * scale) as u8,
+        (c.y() * scale) as u8,
+        (c.z() * scale) as u8,
+        (e + 128) as u8,
+    ]
+}
+
+/// Portable float map: little-endian `f32` RGB with the rows stored bottom to top.
+pub fn write_pfm(w: &mut impl Write, width: u32, height: u32, pixels: &[Color]) -> io::Result<()> {
+    // A negative scale marks little-endian data.
+    write!(w, "PF\n{width} {height}\n-1.0\n")?;
+
+    let mut data = Vec::with_capacity(pixels.len() * 12);
+    for row in pixels.chunks(width as usize).rev() {
+        for p in row {
+            for c in [p.x(), p.y(), p.z()] {
+                data.extend_from_slice(&c.to_le_bytes());
+            }
+        }
+    }
+
+    w.write_all(&data)
+}
+
+/// Scanline OpenEXR with uncompressed 32-bit float `R`, `G` and `B` channels.
+pub fn write_exr(w: &mut impl Write, width: u32, height: u32, pixels: &[Color]) -> io::Result<()> {
+    let mut header = Vec::new();
+
+    // Magic number and version 2, single-part scanline file.
+    header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01]);
+    header.extend_from_slice(&2u32.to_le_bytes());
+
+    let mut channels = Vec::new();
+    // Channels are listed in alphabetical order, and stored in that order within a scanline.
+    for name in [b"B", b"G", b"R"] {
+        channels.extend_from_slice(name);
+        channels.push(0);
+        // Pixel type FLOAT, pLinear and reserved bytes, x and y sampling.
+        channels.extend_from_slice(&2i32.to_le_bytes());
+        channels.extend_from_slice(&[0; 4]);
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+
+    let attribute = |header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]| {
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.extend_from_slice(kind.as_bytes());
+        header.push(0);
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    };
+
+    attribute(&mut header, "channels", "chlist", &channels);
+    // NO_COMPRESSION, so every chunk holds a single scanline.
+    attribute(&mut header, "compression", "compression", &[0]);
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    // INCREASING_Y
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    let line_size = 8 + 12 * width as u64;
+    let first_line = header.len() as u64 + 8 * height as u64;
+
+    // Offset table, from the start of the file to each scanline chunk.
+    for y in 0..height as u64 {
+        header.extend_from_slice(&(first_line + y * line_size).to_le_bytes());
+    }
+    w.write_all(&header)?;
+
+    let mut line = Vec::with_capacity(line_size as usize);
+    for (y, row) in pixels.chunks(width as usize).enumerate() {
+        line.clear();
+        line.extend_from_slice(&(y as i32).to_le_bytes());
+        line.extend_from_slice(&(12 * width).to_le_bytes());
+
+        for channel in [Color::z, Color::y, Color::x] {
+            for p in row {
+                line.extend_from_slice(&channel(p).to_le_bytes());
+            }
+        }
+
+        w.write_all(&line)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_rgbe, write_exr};
+    use crate::vec3::Color;
+
+    #[test]
+    fn rgbe() {
+        assert_eq!(to_rgbe(Color::new(0., 0., 0.)), [0, 0, 0, 0]);
+        assert_eq!(to_rgbe(Color::new(1., 0.5, 0.25)), [128, 64, 32, 129]);
+        assert_eq!(to_rgbe(Color::new(1000., 0., -1.)), [250, 0, 0, 138]);
+        assert_eq!(to_rgbe(Color::new(0.999, 0., 0.)), [255, 0, 0, 128]);
+    }
+
+    #[test]
+    fn exr_layout() {
+        let pixels = vec![Color::new(1., 2., 3.); 6];
+        let mut file = Vec::new();
+        write_exr(&mut file, 3, 2, &pixels).unwrap();
+
+        // Header, 2 offsets and 2 scanlines of 3 pixels with 3 float channels.
+        let lines = 2 * (8 + 3 * 12);
+        let header = file.len() - lines - 2 * 8;
+        let first = u64::from_le_bytes(file[header..header + 8].try_into().unwrap());
+        assert_eq!(first as usize, header + 16);
+
+        // The last scanline starts with its y coordinate and ends with the red channel.
+        let last = &file[file.len() - 44..];
+        assert_eq!(last[..4], 1i32.to_le_bytes());
+        assert_eq!(last[40..], 1f32.to_le_bytes());
+        assert_eq!(last[8..12], 3f32.to_le_bytes());
+    }
+}
@@ -1,30 +1,65 @@
 use crate::{
-    material::Materials,
+    aabb::Aabb,
+    bvh::Bvh,
+    disk::Disk,
+    material::{Material, Materials},
+    medium::{ConstantMedium, GridMedium},
+    plane::Plane,
+    quad::{Cuboid, Quad},
     ray::{Point3, Ray},
+    sampler::ONE_MINUS_EPSILON,
     sphere::Sphere,
-    vec3::Vec3,
+    transform::Instance,
+    triangle::TriangleMesh,
+    vec3::{Color, Vec3},
 };
 
+use fastrand::Rng;
+
 #[derive(Debug, Clone, PartialEq)]
-pub struct HitRecord {
+pub struct HitRecord<'a> {
     pub point: Point3,
     pub normal: Vec3,
-    pub material: Materials,
+    pub material: &'a Materials,
     pub t: f32,
+    /// Surface coordinates, for texture lookups.
+    pub u: f32,
+    pub v: f32,
+    /// Partial derivatives of the point with respect to `u` and `v`, tangent to the surface.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
     pub front_face: bool,
+    /// Radiance given off at the point by the object rather than its material, like the glow
+    /// inside a volume.
+    pub emission: Color,
 }
 
-impl HitRecord {
-    pub fn new(point: Point3, normal: Vec3, t: f32, m: Materials) -> Self {
+impl<'a> HitRecord<'a> {
+    pub fn new(point: Point3, normal: Vec3, t: f32, m: &'a Materials) -> Self {
         Self {
             point,
             normal,
             t,
+            u: 0.,
+            v: 0.,
+            dpdu: Vec3::default(),
+            dpdv: Vec3::default(),
             front_face: false,
+            emission: Color::default(),
             material: m,
         }
     }
 
+    /// Direction the shading frame's x axis follows: `dpdu`, or square to `dpdv` where `dpdu`
+    /// vanishes, like at the center of a disk.
+    pub fn tangent(&self) -> Vec3 {
+        if self.dpdu.len_squared() > 1e-12 * self.dpdv.len_squared() {
+            self.dpdu
+        } else {
+            self.dpdv.cross(self.normal)
+        }
+    }
+
     pub fn set_face_normal(&mut self, r: &Ray) {
         self.front_face = r.direction.dot(self.normal) < 0.;
 
@@ -35,27 +70,238 @@ impl HitRecord {
 }
 
 pub trait Hittable {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord>;
+    /// Closest surface along `r` between `t_min` and `t_max`, passing through participating
+    /// media. Shadow rays use it and account for media with [`Hittable::transmittance`].
+    fn hit_surface(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>>;
+
+    /// Like [`Hittable::hit_surface`], but rays can also scatter inside participating media,
+    /// which draw where they do from `rng`.
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, _rng: &Rng) -> Option<HitRecord<'_>> {
+        self.hit_surface(r, t_min, t_max)
+    }
+
+    /// Fraction of the light along `r` between `t_min` and `t_max` that participating media let
+    /// through, estimated with `rng`. Surfaces are left to [`Hittable::hit_surface`].
+    fn transmittance(&self, _r: &Ray, _t_min: f32, _t_max: f32, _rng: &Rng) -> f32 {
+        1.
+    }
+
+    /// `None` for unbounded objects.
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// Solid angle density of [`Hittable::random`] picking `direction` from `origin` at `time`,
+    /// 0 for objects that can't be sampled as lights.
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3, _time: f32) -> f32 {
+        0.
+    }
+
+    /// Random direction from `origin` towards where the object is at `time`, picked by `u`.
+    fn random(&self, _origin: Point3, _time: f32, _u: (f32, f32)) -> Vec3 {
+        Vec3::new(1., 0., 0.)
+    }
+}
+
+/// Every kind of object a scene can hold. Adding a primitive only takes a variant here, lists
+/// and BVHs hold this enum.
+#[derive(Clone)]
+pub enum Primitives {
+    Sphere(Sphere),
+    Mesh(TriangleMesh),
+    Quad(Quad),
+    Cuboid(Cuboid),
+    Disk(Disk),
+    Plane(Plane),
+    Instance(Instance),
+    Medium(ConstantMedium),
+    Grid(GridMedium),
+}
+
+impl Hittable for Primitives {
+    fn hit_surface(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        match self {
+            Primitives::Sphere(s) => s.hit_surface(r, t_min, t_max),
+            Primitives::Mesh(m) => m.hit_surface(r, t_min, t_max),
+            Primitives::Quad(q) => q.hit_surface(r, t_min, t_max),
+            Primitives::Cuboid(c) => c.hit_surface(r, t_min, t_max),
+            Primitives::Disk(d) => d.hit_surface(r, t_min, t_max),
+            Primitives::Plane(p) => p.hit_surface(r, t_min, t_max),
+            Primitives::Instance(i) => i.hit_surface(r, t_min, t_max),
+            Primitives::Medium(m) => m.hit_surface(r, t_min, t_max),
+            Primitives::Grid(g) => g.hit_surface(r, t_min, t_max),
+        }
+    }
+
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &Rng) -> Option<HitRecord<'_>> {
+        match self {
+            Primitives::Instance(i) => i.hit(r, t_min, t_max, rng),
+            Primitives::Medium(m) => m.hit(r, t_min, t_max, rng),
+            Primitives::Grid(g) => g.hit(r, t_min, t_max, rng),
+            _ => self.hit_surface(r, t_min, t_max),
+        }
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32, rng: &Rng) -> f32 {
+        match self {
+            Primitives::Instance(i) => i.transmittance(r, t_min, t_max, rng),
+            Primitives::Medium(m) => m.transmittance(r, t_min, t_max, rng),
+            Primitives::Grid(g) => g.transmittance(r, t_min, t_max, rng),
+            _ => 1.,
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match self {
+            Primitives::Sphere(s) => s.bounding_box(),
+            Primitives::Mesh(m) => m.bounding_box(),
+            Primitives::Quad(q) => q.bounding_box(),
+            Primitives::Cuboid(c) => c.bounding_box(),
+            Primitives::Disk(d) => d.bounding_box(),
+            Primitives::Plane(p) => p.bounding_box(),
+            Primitives::Instance(i) => i.bounding_box(),
+            Primitives::Medium(m) => m.bounding_box(),
+            Primitives::Grid(g) => g.bounding_box(),
+        }
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f32) -> f32 {
+        match self {
+            Primitives::Sphere(s) => s.pdf_value(origin, direction, time),
+            Primitives::Mesh(m) => m.pdf_value(origin, direction, time),
+            Primitives::Quad(q) => q.pdf_value(origin, direction, time),
+            Primitives::Cuboid(c) => c.pdf_value(origin, direction, time),
+            Primitives::Disk(d) => d.pdf_value(origin, direction, time),
+            Primitives::Plane(p) => p.pdf_value(origin, direction, time),
+            Primitives::Instance(i) => i.pdf_value(origin, direction, time),
+            Primitives::Medium(m) => m.pdf_value(origin, direction, time),
+            Primitives::Grid(g) => g.pdf_value(origin, direction, time),
+        }
+    }
+
+    fn random(&self, origin: Point3, time: f32, u: (f32, f32)) -> Vec3 {
+        match self {
+            Primitives::Sphere(s) => s.random(origin, time, u),
+            Primitives::Mesh(m) => m.random(origin, time, u),
+            Primitives::Quad(q) => q.random(origin, time, u),
+            Primitives::Cuboid(c) => c.random(origin, time, u),
+            Primitives::Disk(d) => d.random(origin, time, u),
+            Primitives::Plane(p) => p.random(origin, time, u),
+            Primitives::Instance(i) => i.random(origin, time, u),
+            Primitives::Medium(m) => m.random(origin, time, u),
+            Primitives::Grid(g) => g.random(origin, time, u),
+        }
+    }
+}
+
+impl Primitives {
+    pub fn material(&self) -> &Materials {
+        match self {
+            Primitives::Sphere(s) => s.material(),
+            Primitives::Mesh(m) => m.material(),
+            Primitives::Quad(q) => q.material(),
+            Primitives::Cuboid(c) => c.material(),
+            Primitives::Disk(d) => d.material(),
+            Primitives::Plane(p) => p.material(),
+            Primitives::Instance(i) => i.material(),
+            Primitives::Medium(m) => m.material(),
+            Primitives::Grid(g) => g.material(),
+        }
+    }
+}
+
+impl From<Sphere> for Primitives {
+    fn from(s: Sphere) -> Self {
+        Primitives::Sphere(s)
+    }
+}
+
+impl From<TriangleMesh> for Primitives {
+    fn from(m: TriangleMesh) -> Self {
+        Primitives::Mesh(m)
+    }
+}
+
+impl From<Quad> for Primitives {
+    fn from(q: Quad) -> Self {
+        Primitives::Quad(q)
+    }
+}
+
+impl From<Cuboid> for Primitives {
+    fn from(c: Cuboid) -> Self {
+        Primitives::Cuboid(c)
+    }
+}
+
+impl From<Disk> for Primitives {
+    fn from(d: Disk) -> Self {
+        Primitives::Disk(d)
+    }
+}
+
+impl From<Plane> for Primitives {
+    fn from(p: Plane) -> Self {
+        Primitives::Plane(p)
+    }
+}
+
+impl From<Instance> for Primitives {
+    fn from(i: Instance) -> Self {
+        Primitives::Instance(i)
+    }
+}
+
+impl From<ConstantMedium> for Primitives {
+    fn from(m: ConstantMedium) -> Self {
+        Primitives::Medium(m)
+    }
+}
+
+impl From<GridMedium> for Primitives {
+    fn from(g: GridMedium) -> Self {
+        Primitives::Grid(g)
+    }
 }
 
 #[derive(Default)]
 pub struct HittableList {
-    objects: Vec<Sphere>,
+    objects: Vec<Primitives>,
 }
 
 impl HittableList {
-    pub fn add(&mut self, o: Sphere) {
-        self.objects.push(o);
+    pub fn add(&mut self, o: impl Into<Primitives>) {
+        self.objects.push(o.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Copies of the emissive objects, to sample as lights. Unbounded ones, like planes, are
+    /// left out since they are too big to sample.
+    pub fn lights(&self) -> HittableList {
+        HittableList {
+            objects: self
+                .objects
+                .iter()
+                .filter(|o| o.material().is_emissive())
+                .filter(|o| o.bounding_box().is_some())
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn into_bvh(self) -> Bvh<Primitives> {
+        Bvh::new(self.objects)
     }
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &Rng) -> Option<HitRecord<'_>> {
         let mut hit_record = None;
         let mut closest_so_far = t_max;
 
         self.objects.iter().for_each(|s| {
-            if let Some(hit) = s.hit(r, t_min, closest_so_far) {
+            if let Some(hit) = s.hit(r, t_min, closest_so_far, rng) {
                 closest_so_far = hit.t;
                 hit_record = Some(hit);
             }
@@ -63,4 +309,52 @@ impl Hittable for HittableList {
 
         hit_record
     }
+
+    fn hit_surface(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let mut hit_record = None;
+        let mut closest_so_far = t_max;
+
+        for o in &self.objects {
+            if let Some(hit) = o.hit_surface(r, t_min, closest_so_far) {
+                closest_so_far = hit.t;
+                hit_record = Some(hit);
+            }
+        }
+
+        hit_record
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32, rng: &Rng) -> f32 {
+        self.objects
+            .iter()
+            .map(|o| o.transmittance(r, t_min, t_max, rng))
+            .product()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.objects.iter().try_fold(Aabb::EMPTY, |acc, o| {
+            o.bounding_box().map(|b| acc.union(&b))
+        })
+    }
+
+    /// Picking each object with the same probability, the density is the average of theirs.
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f32) -> f32 {
+        let sum: f32 = self
+            .objects
+            .iter()
+            .map(|o| o.pdf_value(origin, direction, time))
+            .sum();
+
+        sum / self.objects.len() as f32
+    }
+
+    /// The first coordinate picks the object, and what's left of it once scaled back to [0, 1)
+    /// goes on to sample the object.
+    fn random(&self, origin: Point3, time: f32, u: (f32, f32)) -> Vec3 {
+        let n = self.objects.len();
+        let x = u.0 * n as f32;
+        let i = (x as usize).min(n - 1);
+
+        self.objects[i].random(origin, time, ((x - i as f32).min(ONE_MINUS_EPSILON), u.1))
+    }
 }
@@ -0,0 +1,204 @@
+//! Images read from disk, for textures. PPM (`P3` and `P6`) and PNG files are recognized by
+//! their contents, and their sRGB-encoded pixels are converted to linear radiance.
+
+use crate::{png, vec3::Color};
+
+use std::{fmt, fs, io, path::Path};
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    Format(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Io(e) => write!(f, "{e}"),
+            ImageError::Format(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+impl From<io::Error> for ImageError {
+    fn from(e: io::Error) -> Self {
+        ImageError::Io(e)
+    }
+}
+
+/// Linear RGB pixels, in rows from top to bottom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ImageError> {
+        Self::decode(&fs::read(path)?)
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, ImageError> {
+        let (width, height, samples) = if data.starts_with(b"P3") || data.starts_with(b"P6") {
+            read_ppm(data)?
+        } else if data.starts_with(b"\x89PNG") {
+            let png = png::read(data).map_err(ImageError::Format)?;
+            (png.width as usize, png.height as usize, png.rgb)
+        } else {
+            return Err(ImageError::Format(
+                "unknown image format, expected PPM or PNG".into(),
+            ));
+        };
+
+        if width == 0 || height == 0 {
+            return Err(ImageError::Format("empty image".into()));
+        }
+
+        let pixels = samples
+            .chunks(3)
+            .map(|c| {
+                Color::new(
+                    srgb_to_linear(c[0]),
+                    srgb_to_linear(c[1]),
+                    srgb_to_linear(c[2]),
+                )
+            })
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Inverse of [`crate::output::linear_to_srgb`].
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Number at `*pos` in a PPM-like header, moving `*pos` past it. Header fields are separated by
+/// whitespace, and `#` comments run to the end of the line. `None` if there is no number there.
+pub fn header_field(data: &[u8], pos: &mut usize) -> Option<usize> {
+    loop {
+        match data.get(*pos) {
+            Some(b'#') => {
+                while data.get(*pos).is_some_and(|&b| b != b'\n') {
+                    *pos += 1;
+                }
+            }
+            Some(b) if b.is_ascii_whitespace() => *pos += 1,
+            _ => break,
+        }
+    }
+
+    let start = *pos;
+    while data.get(*pos).is_some_and(u8::is_ascii_digit) {
+        *pos += 1;
+    }
+    std::str::from_utf8(&data[start..*pos]).ok()?.parse().ok()
+}
+
+/// Reads a `P3` or `P6` PPM into samples scaled to [0, 1].
+fn read_ppm(data: &[u8]) -> Result<(usize, usize, Vec<f32>), ImageError> {
+    let err = |message: &str| ImageError::Format(format!("invalid PPM: {message}"));
+
+    let mut pos = 2;
+    let mut field =
+        || header_field(data, &mut pos).ok_or_else(|| err("expected a number in the header"));
+
+    let width = field()?;
+    let height = field()?;
+    let max = field()?;
+    if max == 0 || max > 65535 {
+        return Err(err("maximum value out of range"));
+    }
+
+    let count = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(3))
+        .ok_or_else(|| err("image too large"))?;
+    let scale = 1. / max as f32;
+
+    let samples: Vec<f32> = if data[1] == b'3' {
+        let text = std::str::from_utf8(&data[pos..]).map_err(|_| err("invalid text"))?;
+        text.lines()
+            .map(|line| line.split('#').next().unwrap())
+            .flat_map(str::split_whitespace)
+            .take(count)
+            .map(|w| w.parse::<u32>().map(|v| v as f32 * scale))
+            .collect::<Result<_, _>>()
+            .map_err(|_| err("invalid sample"))?
+    } else {
+        // A single whitespace byte separates the header from the binary samples.
+        let body = data.get(pos + 1..).unwrap_or_default();
+        if max < 256 {
+            body.iter().take(count).map(|&b| b as f32 * scale).collect()
+        } else {
+            body.chunks_exact(2)
+                .take(count)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as f32 * scale)
+                .collect()
+        }
+    };
+
+    if samples.len() < count {
+        return Err(err("not enough pixel data"));
+    }
+
+    Ok((width, height, samples))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{header_field, srgb_to_linear, Image, ImageError};
+    use crate::output::linear_to_srgb;
+
+    #[test]
+    fn ppm() {
+        let ascii = Image::decode(b"P3\n# a comment\n2 1\n255\n255 0 0  0 0 255\n").unwrap();
+        assert_eq!((ascii.width, ascii.height), (2, 1));
+        assert_eq!(ascii.pixel(0, 0).x(), 1.);
+        assert_eq!(ascii.pixel(1, 0).z(), 1.);
+
+        let binary = Image::decode(b"P6 1 2 255\n\xff\x00\x00\x00\xff\x00").unwrap();
+        assert_eq!(binary.pixel(0, 1).y(), 1.);
+
+        assert!(Image::decode(b"P6 2 2 255\n\x00\x00").is_err());
+        assert!(matches!(
+            Image::decode(b"P6 4294967296 4294967296 255\n"),
+            Err(ImageError::Format(m)) if m.ends_with("image too large")
+        ));
+        assert!(Image::decode(b"GIF89a").is_err());
+    }
+
+    #[test]
+    fn header_fields() {
+        let data = b"P6 # a comment\n\t12\n# another\n 34x";
+        let mut pos = 2;
+        assert_eq!(header_field(data, &mut pos), Some(12));
+        assert_eq!(header_field(data, &mut pos), Some(34));
+        assert_eq!(data[pos], b'x');
+        assert_eq!(header_field(data, &mut pos), None);
+    }
+
+    #[test]
+    fn srgb_round_trip() {
+        for i in 0..=20 {
+            let c = i as f32 / 20.;
+            assert!((linear_to_srgb(srgb_to_linear(c)) - c).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! Integrators estimate the radiance arriving along camera rays.
+
+use crate::{
+    background::Background,
+    bsdf::Bsdf,
+    hittable::{HitRecord, Hittable, HittableList},
+    material::Material,
+    ray::Ray,
+    sampler::Sampler,
+    vec3::Color,
+};
+
+use fastrand::Rng;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Integrator {
+    /// Only follows the scattered rays, lights are found by chance.
+    Path,
+    /// Next-event estimation: also samples a light at every non-specular hit, and combines both
+    /// strategies with multiple importance sampling.
+    Nee,
+}
+
+impl Integrator {
+    pub const NAMES: &'static str = "`path` or `nee`";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "path" => Some(Integrator::Path),
+            "nee" => Some(Integrator::Nee),
+            _ => None,
+        }
+    }
+
+    /// `lights` holds the emissive objects of `world`, see [`HittableList::lights`]. Paths end
+    /// after `max_depth` bounces, or earlier by Russian roulette once they are `roulette_depth`
+    /// bounces long. Every bounce takes its sample values from `sampler`, in the order the
+    /// [`sampler`](crate::sampler) module lists, while participating media draw from `rng`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn radiance(
+        self,
+        r: &Ray,
+        world: &impl Hittable,
+        lights: &HittableList,
+        background: &Background,
+        max_depth: u32,
+        roulette_depth: u32,
+        sampler: &mut impl Sampler,
+        rng: &Rng,
+    ) -> Color {
+        let sample_lights = self == Integrator::Nee && !lights.is_empty();
+
+        let mut radiance = Color::default();
+        let mut throughput = Color::new(1., 1., 1.);
+        let mut ray = *r;
+        // Density the previous bounce scattered `ray` with. It's `None` for camera rays and
+        // after specular bounces, since light sampling can't have found what they hit.
+        let mut bsdf_pdf = None;
+
+        for depth in 0..max_depth {
+            let Some(hit) = world.hit(&ray, 0.001, f32::INFINITY, rng) else {
+                radiance += throughput * background.color(ray.direction);
+                break;
+            };
+
+            let mut emitted = hit.material.emitted(&ray, &hit);
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                if emitted != Color::default() {
+                    let light_pdf = lights.pdf_value(ray.origin, ray.direction, ray.time);
+                    emitted = emitted * power_heuristic(bsdf_pdf, light_pdf);
+                }
+            }
+            radiance += throughput * (emitted + hit.emission);
+
+            // Every bounce takes the same dimensions, whether it uses them or not, so that they
+            // line up across the pixel's samples.
+            let light_u = sampler.get_2d();
+            let bsdf_uc = sampler.get_1d();
+            let bsdf_u = sampler.get_2d();
+            let roulette = sampler.get_1d();
+
+            let Some(bsdf) = hit.material.bsdf(&ray, &hit) else {
+                break;
+            };
+            let wo = -ray.direction;
+
+            // The light sample stands in for the emission the scattered ray would find one
+            // bounce further, so it needs that bounce to be within the limit.
+            if sample_lights && bsdf.flags().is_non_specular() && depth + 1 < max_depth {
+                radiance +=
+                    throughput * sample_light(&ray, &hit, &bsdf, world, lights, light_u, rng);
+            }
+
+            let Some(sample) = bsdf.sample(wo, bsdf_uc, bsdf_u) else {
+                break;
+            };
+
+            bsdf_pdf = (sample_lights && !sample.flags.is_specular()).then_some(sample.pdf);
+            throughput = throughput * sample.weight();
+            ray = Ray::with_time(hit.point, sample.wi, ray.time);
+
+            // Ending paths at random, and boosting the survivors by the odds they had, keeps the
+            // estimate unbiased however long paths get.
+            if depth + 1 >= roulette_depth {
+                let survival = throughput.max_component().min(0.95);
+                if survival <= 0. || roulette >= survival {
+                    break;
+                }
+                throughput = throughput / survival;
+            }
+        }
+
+        radiance
+    }
+}
+
+/// Radiance reflected at `hit` from a direction picked by sampling the lights, weighted against
+/// the BSDF finding the same direction.
+fn sample_light(
+    r: &Ray,
+    hit: &HitRecord,
+    bsdf: &Bsdf,
+    world: &impl Hittable,
+    lights: &HittableList,
+    u: (f32, f32),
+    rng: &Rng,
+) -> Color {
+    let direction = lights.random(hit.point, r.time, u);
+    let light_pdf = lights.pdf_value(hit.point, direction, r.time);
+    if light_pdf <= 0. {
+        return Color::default();
+    }
+
+    let f = bsdf.eval(-r.direction, direction);
+    if f == Color::default() {
+        return Color::default();
+    }
+
+    // Whatever surface the shadow ray hits first is what's visible, possibly another light,
+    // dimmed by the media in between.
+    let shadow = Ray::with_time(hit.point, direction, r.time);
+    let Some(light) = world.hit_surface(&shadow, 0.001, f32::INFINITY) else {
+        return Color::default();
+    };
+    let transmittance = world.transmittance(&shadow, 0.001, light.t, rng);
+    if transmittance <= 0. {
+        return Color::default();
+    }
+
+    let bsdf_pdf = bsdf.pdf(-r.direction, direction);
+    let weight = power_heuristic(light_pdf, bsdf_pdf);
+
+    f * light.material.emitted(&shadow, &light) * (transmittance * weight / light_pdf)
+}
+
+/// Weight of a sample taken with density `pdf`, when another strategy could have taken it with
+/// density `other` (Veach, 1997). Written with their ratio, since squaring the huge densities
+/// of lights seen at grazing angles from far away overflows.
+fn power_heuristic(pdf: f32, other: f32) -> f32 {
+    if pdf <= 0. {
+        return 0.;
+    }
+
+    let ratio = other / pdf;
+    1. / (1. + ratio * ratio)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Integrator;
+    use crate::{
+        background::Background,
+        hittable::HittableList,
+        material::{DiffuseLight, Lambertian, Materials},
+        ray::{Point3, Ray},
+        sampler::{Sampler, Sampling},
+        sphere::Sphere,
+        vec3::{Color, Vec3},
+    };
+
+    use fastrand::Rng;
+
+    /// Mean and variance of the red channel over `n` estimates.
+    fn estimate(
+        integrator: Integrator,
+        world: &HittableList,
+        background: &Background,
+        depth: (u32, u32),
+        n: usize,
+    ) -> (f32, f32) {
+        let mut sampler = Sampling::Independent.sampler(1, n as u32);
+        let lights = world.lights();
+        let r = Ray::new(Point3::new(0., 1., 2.), Vec3::new(0., -1., -2.));
+        let rng = Rng::with_seed(1);
+
+        let (mut sum, mut sum_squared) = (0., 0.);
+        for i in 0..n {
+            sampler.start_pixel_sample(0, i as u32);
+            let l = integrator.radiance(
+                &r,
+                world,
+                &lights,
+                background,
+                depth.0,
+                depth.1,
+                &mut sampler,
+                &rng,
+            );
+            sum += l.x() as f64;
+            sum_squared += (l.x() as f64).powi(2);
+        }
+
+        let mean = sum / n as f64;
+        (mean as f32, (sum_squared / n as f64 - mean * mean) as f32)
+    }
+
+    #[test]
+    fn nee_matches_path_tracing_with_less_variance() {
+        let mut world = HittableList::default();
+        world.add(Sphere::new(
+            Point3::new(0., -1000., 0.),
+            1000.,
+            Materials::Lambertian(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        ));
+        world.add(Sphere::new(
+            Point3::new(0., 3., 0.),
+            0.5,
+            Materials::DiffuseLight(DiffuseLight::new(Color::new(10., 10., 10.))),
+        ));
+
+        let black = Background::BLACK;
+        let (path_mean, path_variance) =
+            estimate(Integrator::Path, &world, &black, (2, 2), 400_000);
+        let (nee_mean, nee_variance) = estimate(Integrator::Nee, &world, &black, (2, 2), 20_000);
+
+        assert!(path_mean > 0.);
+        assert!(
+            (nee_mean - path_mean).abs() < 0.05 * path_mean,
+            "{nee_mean} vs {path_mean}"
+        );
+        assert!(
+            nee_variance * 10. < path_variance,
+            "{nee_variance} vs {path_variance}"
+        );
+    }
+
+    #[test]
+    fn russian_roulette_is_unbiased() {
+        // Light bounces between the spheres and the ground before reaching the sky.
+        let mut world = HittableList::default();
+        let white = Materials::Lambertian(Lambertian::new(Color::new(0.9, 0.9, 0.9)));
+        world.add(Sphere::new(
+            Point3::new(0., -1000., 0.),
+            1000.,
+            white.clone(),
+        ));
+        world.add(Sphere::new(
+            Point3::new(-0.6, 0.5, -0.5),
+            0.5,
+            white.clone(),
+        ));
+        world.add(Sphere::new(Point3::new(0.6, 0.5, -0.5), 0.5, white));
+
+        let sky = Background::default();
+        let (exact, _) = estimate(Integrator::Path, &world, &sky, (1000, 1000), 100_000);
+        let (roulette, _) = estimate(Integrator::Path, &world, &sky, (1000, 0), 100_000);
+        let (capped, _) = estimate(Integrator::Path, &world, &sky, (2, 2), 100_000);
+
+        assert!(
+            (roulette - exact).abs() < 0.03 * exact,
+            "{roulette} vs {exact}"
+        );
+        assert!(capped < 0.9 * exact, "{capped} vs {exact}");
+    }
+}
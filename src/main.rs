@@ -1,167 +1,324 @@
-#![feature(portable_simd, lazy_cell)]
+#![feature(portable_simd)]
 
+mod aabb;
+mod adaptive;
+mod background;
+mod bsdf;
+mod bvh;
 mod camera;
+mod cli;
+mod disk;
+mod hdr;
 mod hittable;
+mod image;
+mod integrator;
 mod material;
+mod medium;
+mod microfacet;
+mod obj;
+mod onb;
+mod output;
+mod perlin;
+mod plane;
+mod png;
+mod quad;
+mod random;
 mod ray;
+mod sampler;
+mod scene;
 mod sphere;
+mod texture;
+mod toml;
+mod transform;
+mod triangle;
 mod vec3;
+mod voxels;
 
+use adaptive::PixelEstimate;
+use background::Background;
 use camera::Camera;
-use hittable::HittableList;
-use material::{Dielectric, Lambertian, Materials, Metal};
-use ray::Point3;
-use sphere::Sphere;
-use vec3::{Color, Vec3};
+use cli::Settings;
+use hittable::{Hittable, HittableList};
+use sampler::Sampler;
+use scene::Scene;
+use vec3::Color;
 
-use std::{fs::File, io::Write, sync::LazyLock};
+use std::{env, process};
 
+use fastrand::Rng;
 use rayon::prelude::*;
 
-const ASPECT_RATIO: f32 = 16. / 9.;
-
-#[cfg(debug_assertions)]
-const IMAGE_WIDTH: f32 = 400.0;
-
-#[cfg(not(debug_assertions))]
-const IMAGE_WIDTH: f32 = 1920.0;
-
-const IMAGE_HEIGHT: f32 = IMAGE_WIDTH / ASPECT_RATIO;
-
-#[cfg(debug_assertions)]
-const SAMPLES_PER_PIXEL: usize = 10;
-
-#[cfg(not(debug_assertions))]
-//const SAMPLES_PER_PIXEL: usize = 512;
-const SAMPLES_PER_PIXEL: usize = 10;
-
-const MULTIPLICATIVE_INVERSE_OF_SAMPLES_PER_PIXEL: f32 = 1. / SAMPLES_PER_PIXEL as f32;
-
-#[cfg(debug_assertions)]
-const MAX_DEPTH: u8 = 4;
-
-#[cfg(not(debug_assertions))]
-//const MAX_DEPTH: u8 = 64;
-const MAX_DEPTH: u8 = 6;
-
-static WORLD: LazyLock<HittableList> = LazyLock::new(random_scene);
-
 fn main() {
-    // Camera
-    let lookfrom = Point3::new(13., 2., 3.);
-    let lookat = Point3::new(0., 0., 0.);
-    let vup = Point3::new(0., 1., 0.);
-    let dist_to_focus = 10.;
-    let aperture = 0.1;
+    let settings = match cli::parse(env::args().skip(1)) {
+        Ok(cli::Command::Render(settings)) => settings,
+        Ok(cli::Command::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {e}\n\n{}", cli::USAGE);
+            process::exit(2);
+        }
+    };
 
-    let camera = Camera::new(lookfrom, lookat, vup, 20., aperture, dist_to_focus);
+    if let Some(threads) = settings.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .unwrap();
+    }
+
+    // Scene, either from the given file or the random one.
+    let Scene {
+        camera,
+        world,
+        lights,
+        background,
+    } = match &settings.scene {
+        Some(path) => Scene::load(path, settings.aspect_ratio).unwrap_or_else(|e| {
+            eprintln!("{}: {e}", path.display());
+            process::exit(1);
+        }),
+        None => scene::random_scene(settings.aspect_ratio, &Rng::with_seed(settings.seed)),
+    };
+
+    let world = world.into_bvh();
+    if settings.bvh_stats {
+        eprintln!("{}", world.stats());
+    }
 
     eprintln!(
         "Pixels to generate:{}x{} =  {}",
-        IMAGE_WIDTH,
-        IMAGE_HEIGHT,
-        IMAGE_WIDTH * IMAGE_HEIGHT
+        settings.width,
+        settings.height(),
+        settings.width * settings.height()
     );
 
-    let coords: Vec<_> = (0..(IMAGE_HEIGHT as u32))
+    let estimates = render(&settings, &camera, &world, &lights, &background);
+
+    let pixels: Vec<_> = estimates.iter().map(PixelEstimate::color).collect();
+    let mut images = vec![(&settings.output, settings.format, pixels)];
+    if let Some((path, format)) = &settings.spp_map {
+        let most = settings.samples_per_pixel as f32;
+        let fractions = estimates
+            .iter()
+            .map(|e| Color::splat(e.samples() as f32 / most))
+            .collect();
+        images.push((path, *format, fractions));
+    }
+
+    for (path, format, pixels) in images {
+        if let Err(e) =
+            output::write_image(path, format, settings.width, settings.height(), &pixels)
+        {
+            eprintln!("{}: {e}", path.display());
+            process::exit(1);
+        }
+    }
+}
+
+/// Pixels of the image, in rows from top to bottom. Every sample takes its numbers from the
+/// sampler and a generator for media, which only depend on `settings.seed`, the pixel and the
+/// sample's index.
+///
+/// With `settings.adaptive`, every `settings.min_samples_per_pixel` samples a pixel checks
+/// whether its error is low enough to stop, before it reaches `settings.samples_per_pixel`.
+fn render(
+    settings: &Settings,
+    camera: &Camera,
+    world: &(impl Hittable + Sync),
+    lights: &HittableList,
+    background: &Background,
+) -> Vec<PixelEstimate> {
+    let image_width = settings.width as f32;
+    let image_height = settings.height() as f32;
+
+    let coords: Vec<_> = (0..settings.height())
         .rev()
-        .flat_map(|x| (0..(IMAGE_WIDTH as u32)).map(move |y| (x, y)))
+        .flat_map(|x| (0..settings.width).map(move |y| (x, y)))
         .collect();
 
-    let rng = fastrand::Rng::new();
-    let pixels: Vec<_> = coords
+    coords
         .into_par_iter()
-        .map_with(rng, |r, (x, y)| {
-            let mut pixel_color = Color::default();
+        .map(|(x, y)| {
+            let pixel = x as u64 * settings.width as u64 + y as u64;
+            let mut estimate = PixelEstimate::default();
+            let mut sampler = settings
+                .sampler
+                .sampler(settings.seed, settings.samples_per_pixel);
             let x = x as f32;
             let y = y as f32;
 
-            for _ in 0..SAMPLES_PER_PIXEL {
-                let u = (y + r.f32()) / IMAGE_WIDTH;
-                let v = (x + r.f32()) / IMAGE_HEIGHT;
-                let r = camera.ger_ray(u, v);
-
-                pixel_color += r.color(&WORLD, MAX_DEPTH);
+            for sample in 0..settings.samples_per_pixel {
+                sampler.start_pixel_sample(pixel, sample);
+                // Media take as many numbers as their tracking needs, more than the sampler's
+                // dimensions can line up, so they draw from a generator seeded for the sample.
+                // The complement keeps its seed apart from the sampler's hashes.
+                let rng = Rng::with_seed(random::hash(&[!settings.seed, pixel, sample as u64]));
+
+                let (jitter_x, jitter_y) = sampler.get_2d();
+                let lens = sampler.get_2d();
+                let time = sampler.get_1d();
+                let u = (y + jitter_x) / image_width;
+                let v = (x + jitter_y) / image_height;
+                let r = camera.ger_ray(u, v, lens, time);
+
+                estimate.add(settings.integrator.radiance(
+                    &r,
+                    world,
+                    lights,
+                    background,
+                    settings.max_depth,
+                    settings.roulette_depth,
+                    &mut sampler,
+                    &rng,
+                ));
+
+                if let Some(error) = settings.adaptive {
+                    if (sample + 1) % settings.min_samples_per_pixel == 0
+                        && estimate.relative_error() <= error
+                    {
+                        break;
+                    }
+                }
             }
 
-            // Translate to [0,255] value of each color component.
-            ((pixel_color * MULTIPLICATIVE_INVERSE_OF_SAMPLES_PER_PIXEL).sqrt()).clamp(0., 0.999)
-                * 256.0
+            estimate
         })
-        .collect();
-
-    // Write image
-    let mut buf: Vec<u8> = Vec::with_capacity(40_000_000);
-    writeln!(&mut buf, "P3\n{IMAGE_WIDTH} {IMAGE_HEIGHT}\n255").unwrap();
-
-    for pixel in pixels.iter() {
-        let r = pixel.x() as u8;
-        let g = pixel.y() as u8;
-        let b = pixel.z() as u8;
-
-        writeln!(&mut buf, "{r} {g} {b}").unwrap();
-    }
-
-    let mut file = File::create("./image.ppm").unwrap();
-    file.write_all(&buf).unwrap();
+        .collect()
 }
 
-fn random_scene() -> HittableList {
-    let mut world = HittableList::default();
-
-    world.add(Sphere::new(
-        Point3::new(0., -1000., 0.),
-        1000.0,
-        Materials::Lambertian(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
-    ));
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let a = a as f32;
-            let b = b as f32;
-
-            let choose_mat = fastrand::f32();
-            let center = Point3::new(a + 0.9 * fastrand::f32(), 0.2, b + 0.9 * fastrand::f32());
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use crate::{
+        adaptive::PixelEstimate,
+        cli::Settings,
+        integrator::Integrator,
+        scene::{self, Scene},
+    };
+    use std::path::Path;
+
+    use fastrand::Rng;
+    use rayon::ThreadPoolBuilder;
+
+    /// Function rendering `scene` with some settings on a pool of some number of threads.
+    fn renderer(scene: Scene) -> impl Fn(&Settings, usize) -> Vec<PixelEstimate> {
+        let world = scene.world.into_bvh();
+
+        move |settings, threads| {
+            ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| {
+                    render(
+                        settings,
+                        &scene.camera,
+                        &world,
+                        &scene.lights,
+                        &scene.background,
+                    )
+                })
+        }
+    }
 
-            if (center - Point3::new(4., 0.2, 0.)).len() <= 0.8 {
-                continue;
-            }
+    #[test]
+    fn same_image_on_any_number_of_threads() {
+        let image = renderer(scene::random_scene(16. / 9., &Rng::with_seed(3)));
+        let settings = |seed| Settings {
+            width: 24,
+            samples_per_pixel: 4,
+            seed,
+            ..Settings::default()
+        };
+
+        let reference = image(&settings(7), 1);
+        assert_eq!(image(&settings(7), 4), reference);
+        assert_eq!(image(&settings(7), 3), reference);
+        assert_ne!(image(&settings(8), 4), reference);
+    }
 
-            let material = if choose_mat < 0.8 {
-                // Diffuse
-                let albedo = Color::random() * Color::random();
-                Materials::Lambertian(Lambertian::new(albedo))
-            } else if choose_mat < 0.95 {
-                // Metal
-                let albedo = Color::random_in_range(0.5, 1.);
-                let fuzz = vec3::random_f32(0., 0.5, &fastrand::Rng::new());
-                Materials::Metal(Metal::new(albedo, fuzz))
-            } else {
-                // Glass
-                Materials::Dielectric(Dielectric::new(1.5))
-            };
-
-            world.add(Sphere::new(center, 0.2, material));
-        }
+    #[test]
+    fn media_render_the_same_on_any_number_of_threads() {
+        let src = r#"
+            [camera]
+            lookfrom = [0, 2, 8]
+            lookat = [0, 1.4, 0]
+
+            [[material]]
+            name = "lamp"
+            type = "diffuse_light"
+            emit = [10, 10, 9]
+
+            [[material]]
+            name = "smoke"
+            type = "isotropic"
+            albedo = [0.9, 0.9, 0.9]
+
+            [[quad]]
+            q = [-1.5, 5, -1]
+            u = [3, 0, 0]
+            v = [0, 0, 2]
+            material = "lamp"
+
+            [[sphere]]
+            name = "ball"
+            center = [-1.5, 1, 0]
+            radius = 1
+            material = "smoke"
+
+            [[medium]]
+            boundary = "ball"
+            density = 2
+            material = "smoke"
+
+            [[volume]]
+            file = "volumes/plume.vox"
+            min = [0, 0, -1.5]
+            max = [3, 4, 1.5]
+            density = 6
+            material = "smoke"
+        "#;
+        let image = renderer(Scene::parse(src, Path::new("scenes"), 1.).unwrap());
+        let settings = Settings {
+            width: 16,
+            aspect_ratio: 1.,
+            samples_per_pixel: 4,
+            integrator: Integrator::Nee,
+            ..Settings::default()
+        };
+
+        let reference = image(&settings, 1);
+        assert_eq!(image(&settings, 4), reference);
+        assert_eq!(image(&settings, 3), reference);
     }
 
-    world.add(Sphere::new(
-        Vec3::new(0., 1., 0.),
-        1.0,
-        Materials::Dielectric(Dielectric::new(1.5)),
-    ));
-
-    world.add(Sphere::new(
-        Vec3::new(-4., 1., 0.),
-        1.0,
-        Materials::Lambertian(Lambertian::new(Color::new(0.4, 0.2, 0.1))),
-    ));
-
-    world.add(Sphere::new(
-        Vec3::new(3.5, 1., 0.),
-        1.0,
-        Materials::Metal(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0)),
-    ));
-
-    world
+    #[test]
+    fn adaptive_sampling_spends_samples_on_noisy_pixels() {
+        let scene = scene::random_scene(16. / 9., &Rng::with_seed(3));
+        let world = scene.world.into_bvh();
+
+        let settings = Settings {
+            width: 32,
+            samples_per_pixel: 64,
+            adaptive: Some(0.05),
+            min_samples_per_pixel: 8,
+            ..Settings::default()
+        };
+        let estimates = render(
+            &settings,
+            &scene.camera,
+            &world,
+            &scene.lights,
+            &scene.background,
+        );
+
+        // The smooth sky at the top stops at the first check, and the rest of the picture
+        // takes more.
+        let samples: Vec<_> = estimates.iter().map(|e| e.samples()).collect();
+        assert!(samples.iter().all(|n| n % 8 == 0 && (8..=64).contains(n)));
+        assert!(samples[..32].iter().all(|&n| n == 8), "{samples:?}");
+        assert!(samples.iter().any(|&n| n > 8));
+    }
 }
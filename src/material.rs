@@ -1,113 +1,468 @@
 use crate::{
+    bsdf::{
+        Bsdf, ConductorBxdf, DielectricBxdf, DiffuseBxdf, Fresnel, HenyeyGreenstein, PrincipledBxdf,
+    },
     hittable::HitRecord,
+    microfacet::{Ggx, Gtr1},
     ray::Ray,
-    sphere::{random_in_unit_sphere, random_unit_vector},
+    texture::{Texture, Textures},
     vec3::Color,
 };
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Materials {
     Lambertian(Lambertian),
     Metal(Metal),
     Dielectric(Dielectric),
+    Principled(Principled),
+    DiffuseLight(DiffuseLight),
+    Phase(Phase),
 }
 
 pub trait Material {
-    fn scatter(self, ray_in: &Ray, rec: HitRecord) -> Option<(Ray, Color)>;
+    /// How the surface scatters light at the hit, which `ray_in` looks at. `None` for
+    /// materials that don't scatter any.
+    fn bsdf(&self, ray_in: &Ray, rec: &HitRecord) -> Option<Bsdf>;
+
+    /// Radiance emitted towards `ray_in` at the hit, black for anything but lights.
+    fn emitted(&self, _ray_in: &Ray, _rec: &HitRecord) -> Color {
+        Color::default()
+    }
+
+    /// Whether the material emits any light, so that it's worth sampling as a light.
+    fn is_emissive(&self) -> bool {
+        false
+    }
 }
 
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+impl Material for Materials {
+    fn bsdf(&self, ray_in: &Ray, rec: &HitRecord) -> Option<Bsdf> {
+        match self {
+            Materials::Lambertian(l) => l.bsdf(ray_in, rec),
+            Materials::Metal(m) => m.bsdf(ray_in, rec),
+            Materials::Dielectric(d) => d.bsdf(ray_in, rec),
+            Materials::Principled(p) => p.bsdf(ray_in, rec),
+            Materials::DiffuseLight(d) => d.bsdf(ray_in, rec),
+            Materials::Phase(p) => p.bsdf(ray_in, rec),
+        }
+    }
+
+    fn emitted(&self, ray_in: &Ray, rec: &HitRecord) -> Color {
+        match self {
+            Materials::Lambertian(l) => l.emitted(ray_in, rec),
+            Materials::Metal(m) => m.emitted(ray_in, rec),
+            Materials::Dielectric(d) => d.emitted(ray_in, rec),
+            Materials::Principled(p) => p.emitted(ray_in, rec),
+            Materials::DiffuseLight(d) => d.emitted(ray_in, rec),
+            Materials::Phase(p) => p.emitted(ray_in, rec),
+        }
+    }
+
+    fn is_emissive(&self) -> bool {
+        match self {
+            Materials::Lambertian(l) => l.is_emissive(),
+            Materials::Metal(m) => m.is_emissive(),
+            Materials::Dielectric(d) => d.is_emissive(),
+            Materials::Principled(p) => p.is_emissive(),
+            Materials::DiffuseLight(d) => d.is_emissive(),
+            Materials::Phase(p) => p.is_emissive(),
+        }
+    }
+}
+
+/// Diffuse surfaces, smooth or rough, see [`DiffuseBxdf`].
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Textures,
+    /// Standard deviation of the angle of the surface's grooves, in radians.
+    sigma: f32,
 }
 
 impl Lambertian {
-    pub fn new(albedo: Color) -> Self {
-        Self { albedo }
+    pub fn new(albedo: impl Into<Textures>) -> Self {
+        Self {
+            albedo: albedo.into(),
+            sigma: 0.,
+        }
+    }
+
+    /// Roughness as the standard deviation of the angle of the surface's grooves, in degrees.
+    /// Rough surfaces look flat, like clay or the moon, from 20° or so.
+    pub fn with_sigma(self, degrees: f32) -> Self {
+        Self {
+            sigma: degrees.to_radians(),
+            ..self
+        }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(self, _ray_in: &Ray, rec: HitRecord) -> Option<(Ray, Color)> {
-        let mut scatter_direction = rec.normal + random_unit_vector();
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
-        }
+    fn bsdf(&self, _ray_in: &Ray, rec: &HitRecord) -> Option<Bsdf> {
+        let albedo = self.albedo.value(rec.u, rec.v, rec.point);
+        let diffuse = DiffuseBxdf::oren_nayar(albedo, self.sigma);
 
-        Some((Ray::new(rec.point, scatter_direction), self.albedo))
+        Some(Bsdf::new(rec.normal, rec.tangent(), diffuse))
     }
 }
 
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+/// How much light a metal reflects, depending on the angle it arrives at.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reflectance {
+    /// The color at normal incidence, which grazing angles turn white by Schlick's
+    /// approximation.
+    Color(Textures),
+    /// Complex index of refraction `eta + ik`, per channel, of a measured metal.
+    Conductor { eta: Color, k: Color },
+}
+
+/// A conductor: smooth as a mirror, or rough with GGX microfacets.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Metal {
-    albedo: Color,
-    fuzz: f32,
+    reflectance: Reflectance,
+    roughness: Ggx,
 }
 
 impl Metal {
-    pub fn new(albedo: Color, fuzz: f32) -> Self {
+    pub const PRESETS: &'static str = "`gold`, `copper`, `aluminium` or `silver`";
+
+    pub fn new(albedo: impl Into<Textures>) -> Self {
         Self {
-            albedo,
-            fuzz: fuzz.clamp(std::f32::MIN, 1.),
+            reflectance: Reflectance::Color(albedo.into()),
+            roughness: Ggx::from_roughness(0., 0.),
         }
     }
-}
 
-impl Material for Metal {
-    fn scatter(self, ray_in: &Ray, rec: HitRecord) -> Option<(Ray, Color)> {
-        let reflected = ray_in.direction.unit_vector().reflect(rec.normal);
-        let scattered = Ray::new(
-            rec.point,
-            random_in_unit_sphere().mul_add_vec(self.fuzz, reflected),
-        );
+    pub fn conductor(eta: Color, k: Color) -> Self {
+        Self {
+            reflectance: Reflectance::Conductor { eta, k },
+            roughness: Ggx::from_roughness(0., 0.),
+        }
+    }
 
-        if scattered.direction.dot(rec.normal) > 0. {
-            Some((scattered, self.albedo))
-        } else {
-            None
+    /// Indices of refraction of common metals, fitted to the red, green and blue primaries.
+    pub fn preset(name: &str) -> Option<Self> {
+        let (eta, k) = match name {
+            "gold" => ((0.143, 0.374, 1.442), (3.983, 2.385, 1.603)),
+            "copper" => ((0.200, 0.924, 1.102), (3.912, 2.452, 2.142)),
+            "aluminium" => ((1.657, 0.880, 0.521), (9.224, 6.270, 4.837)),
+            "silver" => ((0.155, 0.117, 0.138), (4.828, 3.122, 2.147)),
+            _ => return None,
+        };
+
+        Some(Self::conductor(
+            Color::new(eta.0, eta.1, eta.2),
+            Color::new(k.0, k.1, k.2),
+        ))
+    }
+
+    /// Perceptual roughness from 0, a mirror, to 1, along the surface's `u` and `v` directions.
+    /// Different values stretch highlights, like on brushed metal.
+    pub fn with_roughness(self, along_u: f32, along_v: f32) -> Self {
+        Self {
+            roughness: Ggx::from_roughness(along_u, along_v),
+            ..self
+        }
+    }
+
+    fn fresnel(&self, rec: &HitRecord) -> Fresnel {
+        match &self.reflectance {
+            Reflectance::Color(albedo) => Fresnel::Schlick(albedo.value(rec.u, rec.v, rec.point)),
+            Reflectance::Conductor { eta, k } => Fresnel::Conductor { eta: *eta, k: *k },
         }
     }
 }
 
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+impl Material for Metal {
+    fn bsdf(&self, _ray_in: &Ray, rec: &HitRecord) -> Option<Bsdf> {
+        let conductor = ConductorBxdf::new(self.fresnel(rec), self.roughness);
+        Some(Bsdf::new(rec.normal, rec.tangent(), conductor))
+    }
+}
+
+/// Glass, water and the like: smooth, or rough like frosted glass, see [`DielectricBxdf`].
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Dielectric {
     index_of_refraction: f32,
+    roughness: Ggx,
+    /// Color light is filtered by every time it goes through the surface.
+    tint: Color,
+    /// Fraction of the light absorbed per unit of distance inside, per channel. After `d`,
+    /// `exp(-absorption * d)` is left (Beer-Lambert law).
+    absorption: Color,
 }
 
 impl Dielectric {
     pub fn new(index_of_refraction: f32) -> Self {
         Self {
             index_of_refraction,
+            roughness: Ggx::from_roughness(0., 0.),
+            tint: Color::new(1., 1., 1.),
+            absorption: Color::default(),
+        }
+    }
+
+    /// Perceptual roughness from 0, smooth, to 1, along the surface's `u` and `v` directions.
+    pub fn with_roughness(self, along_u: f32, along_v: f32) -> Self {
+        Self {
+            roughness: Ggx::from_roughness(along_u, along_v),
+            ..self
         }
     }
 
-    /// Use Schlick's approximation for reflectance
-    fn reflectance(&self, cosine: f32, ref_idx: f32) -> f32 {
-        let r0 = (1. - ref_idx) / (1. + ref_idx).powi(2);
+    pub fn with_tint(self, tint: Color) -> Self {
+        Self { tint, ..self }
+    }
+
+    pub fn with_absorption(self, absorption: Color) -> Self {
+        Self { absorption, ..self }
+    }
+
+    /// Ratio of the index of refraction on the far side of the surface to the one on the side
+    /// of the normal, which faces the ray.
+    fn eta(&self, rec: &HitRecord) -> f32 {
+        if rec.front_face {
+            self.index_of_refraction
+        } else {
+            1. / self.index_of_refraction
+        }
+    }
+
+    /// What's left of the light that went through the inside to reach the back of the surface.
+    /// It's the same whichever way the light goes on, so it's part of every direction's weight.
+    fn transmittance(&self, ray_in: &Ray, rec: &HitRecord) -> Color {
+        if rec.front_face || self.absorption == Color::default() {
+            return Color::new(1., 1., 1.);
+        }
 
-        r0 + (1. - r0) * (1. - cosine).powi(5)
+        (-self.absorption * (rec.t * ray_in.direction.len())).exp()
     }
 }
 
 impl Material for Dielectric {
-    fn scatter(self, ray_in: &Ray, rec: HitRecord) -> Option<(Ray, Color)> {
-        let refraction_ratio = if rec.front_face {
-            1.0 / self.index_of_refraction
+    fn bsdf(&self, ray_in: &Ray, rec: &HitRecord) -> Option<Bsdf> {
+        let dielectric = DielectricBxdf::new(
+            self.eta(rec),
+            self.roughness,
+            self.tint,
+            self.transmittance(ray_in, rec),
+        );
+
+        Some(Bsdf::new(rec.normal, rec.tangent(), dielectric))
+    }
+}
+
+/// Disney's principled BSDF (Burley, "Physically Based Shading at Disney", 2012), which blends
+/// diffuse, metallic, glossy and glassy surfaces with a handful of parameters, most of them in
+/// [0, 1], instead of having to pick a material for each. Its specular and glass lobes are
+/// those of [`Metal`] and [`Dielectric`], see [`PrincipledBxdf`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Principled {
+    pub base_color: Textures,
+    /// From 0, a dielectric, to 1, a metal whose reflections take the base color.
+    pub metallic: f32,
+    /// Perceptual roughness along the surface's `u` and `v` directions, as for [`Metal`].
+    pub roughness: (f32, f32),
+    /// Reflectance of dielectrics at normal incidence, 1 being 8%. The default of 0.5 is that
+    /// of an index of refraction of 1.5.
+    pub specular: f32,
+    /// How much the reflections of dielectrics take the hue of the base color.
+    pub specular_tint: f32,
+    /// Extra reflection at grazing angles, as on cloth.
+    pub sheen: f32,
+    /// How much the sheen takes the hue of the base color.
+    pub sheen_tint: f32,
+    /// Strength of a second, white and glossy specular layer, like varnish.
+    pub clearcoat: f32,
+    /// From 0, a satin clearcoat, to 1, a glossy one.
+    pub clearcoat_gloss: f32,
+    /// Fraction of the dielectric that lets light through like glass, tinted by the base color.
+    pub transmission: f32,
+    /// Index of refraction of the transmission.
+    pub ior: f32,
+    /// Radiance emitted from the front face.
+    pub emission: Color,
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Self {
+            base_color: Textures::Solid(Color::new(0.8, 0.8, 0.8)),
+            metallic: 0.,
+            roughness: (0.5, 0.5),
+            specular: 0.5,
+            specular_tint: 0.,
+            sheen: 0.,
+            sheen_tint: 0.5,
+            clearcoat: 0.,
+            clearcoat_gloss: 1.,
+            transmission: 0.,
+            ior: 1.5,
+            emission: Color::default(),
+        }
+    }
+}
+
+impl Principled {
+    /// Roughness below which the specular lobes are too sharp to evaluate, since light
+    /// sampling needs to.
+    const MIN_ROUGHNESS: f32 = 0.05;
+}
+
+impl Material for Principled {
+    fn bsdf(&self, _ray_in: &Ray, rec: &HitRecord) -> Option<Bsdf> {
+        let base = self.base_color.value(rec.u, rec.v, rec.point);
+        let luminance = base.luminance();
+        let hue = if luminance > 0. {
+            base / luminance
         } else {
-            self.index_of_refraction
+            Color::new(1., 1., 1.)
         };
+        let white = Color::new(1., 1., 1.);
+
+        let dielectric_f0 = lerp(white, hue, self.specular_tint) * (0.08 * self.specular);
+        let f0 = lerp(dielectric_f0, base, self.metallic);
+        let roughness = Ggx::from_roughness(
+            self.roughness.0.max(Self::MIN_ROUGHNESS),
+            self.roughness.1.max(Self::MIN_ROUGHNESS),
+        );
+        let eta = if rec.front_face {
+            self.ior
+        } else {
+            1. / self.ior
+        };
+
+        let dielectric = 1. - self.metallic;
+        let glass = dielectric * self.transmission;
+        let principled = PrincipledBxdf::new(
+            base,
+            (self.roughness.0 + self.roughness.1) / 2.,
+            lerp(white, hue, self.sheen_tint) * self.sheen,
+            ConductorBxdf::new(Fresnel::Schlick(f0), roughness),
+            DielectricBxdf::new(eta, roughness, base, white),
+            Gtr1::new(0.1 + (0.001 - 0.1) * self.clearcoat_gloss),
+            [
+                dielectric * (1. - self.transmission),
+                1. - glass,
+                glass,
+                0.25 * self.clearcoat,
+            ],
+        );
+
+        Some(Bsdf::new(rec.normal, rec.tangent(), principled))
+    }
+
+    fn emitted(&self, _ray_in: &Ray, rec: &HitRecord) -> Color {
+        if rec.front_face {
+            self.emission
+        } else {
+            Color::default()
+        }
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.emission != Color::default()
+    }
+}
+
+/// `a` blended into `b` as `t` goes from 0 to 1.
+fn lerp(a: Color, b: Color, t: f32) -> Color {
+    a * (1. - t) + b * t
+}
+
+/// Emits light from the front face of the surface and doesn't reflect any.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn bsdf(&self, _ray_in: &Ray, _rec: &HitRecord) -> Option<Bsdf> {
+        None
+    }
 
-        let unit_direction = ray_in.direction.unit_vector();
-        let cos_theta = (-unit_direction).dot(rec.normal).min(1.0);
-        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
-        let direction = if refraction_ratio * sin_theta > 1.0
-            || self.reflectance(cos_theta, refraction_ratio) > fastrand::f32()
-        {
-            unit_direction.reflect(rec.normal) // No refraction
+    fn emitted(&self, _ray_in: &Ray, rec: &HitRecord) -> Color {
+        if rec.front_face {
+            self.emit
         } else {
-            unit_direction.refract(rec.normal, refraction_ratio) // No reflection
+            Color::default()
+        }
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.emit != Color::default()
+    }
+}
+
+/// Scattering inside participating media, see [`crate::medium::ConstantMedium`], by the
+/// Henyey–Greenstein phase function. Directions are relative to the ray, surface normals don't
+/// matter.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Phase {
+    albedo: Textures,
+    g: f32,
+}
+
+impl Phase {
+    pub fn isotropic(albedo: impl Into<Textures>) -> Self {
+        Self::henyey_greenstein(albedo, 0.)
+    }
+
+    /// `g` is the average cosine between the ray and the scattered direction, in (-1, 1).
+    pub fn henyey_greenstein(albedo: impl Into<Textures>, g: f32) -> Self {
+        Self {
+            albedo: albedo.into(),
+            g,
+        }
+    }
+}
+
+impl Material for Phase {
+    fn bsdf(&self, _ray_in: &Ray, rec: &HitRecord) -> Option<Bsdf> {
+        let albedo = self.albedo.value(rec.u, rec.v, rec.point);
+        let phase = HenyeyGreenstein::new(albedo, self.g);
+
+        Some(Bsdf::new(rec.normal, rec.tangent(), phase))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dielectric, Material, Materials};
+    use crate::{
+        hittable::HitRecord,
+        ray::{Point3, Ray},
+        vec3::{Color, Vec3},
+    };
+
+    #[test]
+    fn absorption_inside_dielectrics() {
+        let material =
+            Materials::Dielectric(Dielectric::new(1.5).with_absorption(Color::new(0., 0.5, 1.)));
+        let ray = Ray::new(Point3::default(), Vec3::new(0., 0., 2.));
+        let mut rec = HitRecord::new(
+            Point3::new(0., 0., 3.),
+            Vec3::new(0., 0., -1.),
+            1.5,
+            &material,
+        );
+        let weight = |rec: &HitRecord| {
+            let bsdf = material.bsdf(&ray, rec).unwrap();
+            bsdf.sample(-ray.direction, 0.5, (0.5, 0.5))
+                .unwrap()
+                .weight()
         };
 
-        Some((Ray::new(rec.point, direction), Color::new(1., 1., 1.)))
+        // The ray went 3 units through the glass to get to the back of the surface.
+        let expected = Color::new(1., (-1.5f32).exp(), (-3f32).exp());
+        assert!((weight(&rec) - expected).len() < 1e-6, "{:?}", weight(&rec));
+
+        rec.front_face = true;
+        assert_eq!(weight(&rec), Color::new(1., 1., 1.));
     }
 }
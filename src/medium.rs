@@ -0,0 +1,323 @@
+//! Participating media, like fog and smoke, that fill a closed boundary.
+//!
+//! Media pick where rays scatter while the rays are traced, so [`Hittable::hit`] and
+//! [`Hittable::transmittance`] take the random number generator of the sample being rendered.
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable, Primitives},
+    material::Materials,
+    ray::{Point3, Ray},
+    vec3::{Color, Vec3},
+    voxels::{Channel, VoxelGrid},
+};
+
+use std::sync::Arc;
+
+use fastrand::Rng;
+
+/// Medium of the same density everywhere inside `boundary`. Rays scatter after a random
+/// distance through it, which is exponentially distributed, or pass through.
+#[derive(Clone)]
+pub struct ConstantMedium {
+    boundary: Arc<Primitives>,
+    neg_inv_density: f32,
+    /// Usually a [`crate::material::Phase`].
+    phase: Materials,
+}
+
+impl ConstantMedium {
+    /// `density` is the chance of scattering per unit of distance.
+    pub fn new(boundary: Arc<Primitives>, density: f32, phase: Materials) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1. / density,
+            phase,
+        }
+    }
+
+    pub fn material(&self) -> &Materials {
+        &self.phase
+    }
+}
+
+impl Hittable for ConstantMedium {
+    /// The boundary is walked from one crossing to the next, so the ray can go in and out of
+    /// a boundary that isn't convex. Each stretch inside gets its own flight distance, which
+    /// is fine since the exponential distribution has no memory.
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &Rng) -> Option<HitRecord<'_>> {
+        let length = r.direction.len();
+        let mut from = f32::NEG_INFINITY;
+
+        loop {
+            let enter = self.boundary.hit_surface(r, from, f32::INFINITY)?.t;
+            let exit = self.boundary.hit_surface(r, enter + 1e-4, f32::INFINITY)?.t;
+
+            let start = enter.max(t_min);
+            let end = exit.min(t_max);
+            if start < end {
+                let distance = self.neg_inv_density * rng.f32().ln();
+                if distance < (end - start) * length {
+                    let t = start + distance / length;
+                    let mut hr = HitRecord::new(r.at(t), Vec3::new(1., 0., 0.), t, &self.phase);
+                    hr.front_face = true;
+
+                    return Some(hr);
+                }
+            }
+
+            if exit >= t_max {
+                return None;
+            }
+            from = exit + 1e-4;
+        }
+    }
+
+    fn hit_surface(&self, _r: &Ray, _t_min: f32, _t_max: f32) -> Option<HitRecord<'_>> {
+        None
+    }
+
+    /// The density being constant, it's the exponential of the distance inside.
+    fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32, _rng: &Rng) -> f32 {
+        let mut inside = 0.;
+        let mut from = f32::NEG_INFINITY;
+
+        while let Some(enter) = self.boundary.hit_surface(r, from, f32::INFINITY) {
+            let Some(exit) = self.boundary.hit_surface(r, enter.t + 1e-4, f32::INFINITY) else {
+                break;
+            };
+
+            inside += (exit.t.min(t_max) - enter.t.max(t_min)).max(0.);
+            if exit.t >= t_max {
+                break;
+            }
+            from = exit.t + 1e-4;
+        }
+
+        (inside * r.direction.len() / self.neg_inv_density).exp()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+/// Medium whose density varies through a voxel grid stretched over a box, and which can glow.
+/// Distances are sampled by delta tracking: tentative collisions are drawn as if the whole box
+/// were as dense as the densest voxel, and each one is real with the odds of the actual density
+/// against that (Woodcock et al., 1965).
+#[derive(Clone)]
+pub struct GridMedium {
+    grid: Arc<VoxelGrid>,
+    bounds: Aabb,
+    /// Densities of the grid are multiplied by this.
+    density: f32,
+    /// Radiance of parts too dense to see through, where the grid's emission is 1.
+    emission: Color,
+    phase: Materials,
+}
+
+impl GridMedium {
+    pub fn new(
+        grid: Arc<VoxelGrid>,
+        bounds: Aabb,
+        density: f32,
+        emission: Color,
+        phase: Materials,
+    ) -> Self {
+        Self {
+            grid,
+            bounds,
+            density,
+            emission,
+            phase,
+        }
+    }
+
+    pub fn material(&self) -> &Materials {
+        &self.phase
+    }
+
+    /// Where the grid lookups happen, from 0 to 1 across the box.
+    fn grid_point(&self, p: Point3) -> Point3 {
+        (p - self.bounds.min) / self.bounds.extent()
+    }
+
+    /// Density of the densest voxel, which bounds the density everywhere in the box.
+    fn majorant(&self) -> f32 {
+        self.density * self.grid.max_density()
+    }
+
+    /// The part of `r` between `t_min` and `t_max` inside the box, `None` if it misses the box
+    /// or there is nothing in it.
+    fn overlap(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        if self.majorant() <= 0. {
+            return None;
+        }
+
+        let d = r.direction;
+        let inv_direction = Vec3::new(1. / d.x(), 1. / d.y(), 1. / d.z());
+        self.bounds.overlap(r.origin, inv_direction, t_min, t_max)
+    }
+}
+
+impl Hittable for GridMedium {
+    /// The glow is given off by what the medium is made of, so it's picked up at real
+    /// collisions, and only shows where there is some density.
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &Rng) -> Option<HitRecord<'_>> {
+        let (mut t, end) = self.overlap(r, t_min, t_max)?;
+        let majorant = self.majorant();
+        let rate = majorant * r.direction.len();
+
+        loop {
+            t -= rng.f32().ln() / rate;
+            if t >= end {
+                return None;
+            }
+
+            let point = r.at(t);
+            let p = self.grid_point(point);
+            if rng.f32() * majorant < self.density * self.grid.lookup(p, Channel::Density) {
+                let mut hr = HitRecord::new(point, Vec3::new(1., 0., 0.), t, &self.phase);
+                hr.front_face = true;
+                hr.emission = self.emission * self.grid.lookup(p, Channel::Emission);
+
+                return Some(hr);
+            }
+        }
+    }
+
+    fn hit_surface(&self, _r: &Ray, _t_min: f32, _t_max: f32) -> Option<HitRecord<'_>> {
+        None
+    }
+
+    /// Ratio tracking (Novák et al., 2014): rather than stopping at the first real collision,
+    /// every tentative one scales the estimate by the chance it wasn't real.
+    fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32, rng: &Rng) -> f32 {
+        let Some((mut t, end)) = self.overlap(r, t_min, t_max) else {
+            return 1.;
+        };
+        let majorant = self.majorant();
+        let rate = majorant * r.direction.len();
+
+        let mut transmittance = 1.;
+        loop {
+            t -= rng.f32().ln() / rate;
+            if t >= end {
+                return transmittance;
+            }
+
+            let density =
+                self.density * self.grid.lookup(self.grid_point(r.at(t)), Channel::Density);
+            transmittance *= 1. - density / majorant;
+
+            // Once little light is left, Russian roulette keeps thick media from taking many
+            // steps for nothing.
+            if transmittance < 0.1 {
+                if rng.bool() {
+                    return 0.;
+                }
+                transmittance *= 2.;
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConstantMedium, GridMedium};
+    use crate::{
+        aabb::Aabb,
+        hittable::Hittable,
+        material::{Materials, Phase},
+        quad::Cuboid,
+        ray::{Point3, Ray},
+        vec3::{Color, Vec3},
+        voxels::VoxelGrid,
+    };
+    use std::sync::Arc;
+
+    use fastrand::Rng;
+
+    #[test]
+    fn transmittance_is_exponential() {
+        let phase = Materials::Phase(Phase::isotropic(Color::new(1., 1., 1.)));
+        let boundary = Cuboid::new(
+            Point3::new(0., -1., -1.),
+            Point3::new(2., 1., 1.),
+            phase.clone(),
+        );
+        let fog = ConstantMedium::new(Arc::new(boundary.into()), 0.5, phase);
+        let rng = Rng::with_seed(1);
+
+        // Through the whole box from outside, and half of it from inside.
+        for (origin, expected) in [(-3., (-1f32).exp()), (1., (-0.5f32).exp())] {
+            let r = Ray::new(Point3::new(origin, 0., 0.), Vec3::new(2., 0., 0.));
+            let n = 100_000;
+            let passed = (0..n)
+                .filter(|_| match fog.hit(&r, 0.001, f32::INFINITY, &rng) {
+                    Some(hit) => {
+                        assert!((0. ..=2.).contains(&hit.point.x()) && hit.t > 0.);
+                        false
+                    }
+                    None => true,
+                })
+                .count();
+
+            let fraction = passed as f32 / n as f32;
+            assert!(
+                (fraction - expected).abs() < 0.01,
+                "{fraction} vs {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn tracking_through_a_grid() {
+        // Densities of 1 and 3 along x, averaging 2 across the grid, which glows evenly.
+        let mut data = b"VOXELS 2 1 1 2\n".to_vec();
+        data.extend([1f32, 1., 3., 1.].iter().flat_map(|v| v.to_le_bytes()));
+        let grid = VoxelGrid::decode(&data).unwrap();
+
+        let glow = Color::new(2., 1., 0.5);
+        let volume = GridMedium::new(
+            Arc::new(grid),
+            Aabb::new(Point3::new(0., 0., 0.), Point3::new(2., 1., 1.)),
+            0.5,
+            glow,
+            Materials::Phase(Phase::isotropic(Color::new(1., 1., 1.))),
+        );
+
+        let r = Ray::new(Point3::new(-1., 0.5, 0.5), Vec3::new(2., 0., 0.));
+        let expected = (-2f32).exp();
+        assert!(volume.hit_surface(&r, 0.001, f32::INFINITY).is_none());
+
+        let rng = Rng::with_seed(2);
+        let n = 100_000;
+        let mut passed = 0;
+        let (mut ratio, mut half) = (0., 0.);
+        for _ in 0..n {
+            match volume.hit(&r, 0.001, f32::INFINITY, &rng) {
+                Some(hit) => {
+                    assert!((0. ..=2.).contains(&hit.point.x()));
+                    assert_eq!(hit.emission, glow);
+                }
+                None => passed += 1,
+            }
+            ratio += volume.transmittance(&r, 0.001, f32::INFINITY, &rng);
+            // Stopping at x = 1 only goes through the thinner half.
+            half += volume.transmittance(&r, 0.001, 1., &rng);
+        }
+
+        let delta = passed as f32 / n as f32;
+        let ratio = ratio / n as f32;
+        let half = half / n as f32;
+        assert!((delta - expected).abs() < 0.01, "{delta} vs {expected}");
+        assert!((ratio - expected).abs() < 0.01, "{ratio} vs {expected}");
+        assert!((half - (-0.625f32).exp()).abs() < 0.01, "{half}");
+    }
+}
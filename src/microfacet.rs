@@ -0,0 +1,317 @@
+//! Microfacet models of rough surfaces, seen as many tiny mirrors oriented at random.
+//!
+//! Directions are in a local frame with the surface normal along z and the tangent of the
+//! surface's `u` parameter along x.
+
+use crate::vec3::{Color, Vec3};
+
+use std::f32::consts::PI;
+
+/// The GGX, or Trowbridge-Reitz, distribution of microfacet normals, with Smith's masking and
+/// shadowing. `alpha_x` and `alpha_y` are its roughness along x and y, about the slope of a
+/// typical microfacet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ggx {
+    alpha_x: f32,
+    alpha_y: f32,
+}
+
+impl Ggx {
+    /// Smallest roughness along either axis, since the distribution divides by it. A surface
+    /// that is only smooth along one of them is still rough.
+    const MIN_ALPHA: f32 = 1e-4;
+
+    /// From perceptual roughnesses in [0, 1], which look about linear, along x and y.
+    pub fn from_roughness(x: f32, y: f32) -> Self {
+        Self {
+            alpha_x: x.clamp(0., 1.).powi(2).max(Self::MIN_ALPHA),
+            alpha_y: y.clamp(0., 1.).powi(2).max(Self::MIN_ALPHA),
+        }
+    }
+
+    /// Whether the surface is so smooth that it's better handled as a perfect mirror, since the
+    /// distribution gets too sharp to evaluate.
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    /// Density of microfacet normals `wm`, per unit of projected area.
+    pub fn d(&self, wm: Vec3) -> f32 {
+        if wm.z() <= 0. {
+            return 0.;
+        }
+
+        let (x, y) = (wm.x() / self.alpha_x, wm.y() / self.alpha_y);
+        let e = x * x + y * y + wm.z() * wm.z();
+
+        1. / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    /// Smith's Λ, the area of microfacets hidden from `w` relative to the visible area.
+    fn lambda(&self, w: Vec3) -> f32 {
+        let z2 = w.z() * w.z();
+        if z2 == 0. {
+            return f32::INFINITY;
+        }
+
+        let a2 = (self.alpha_x * w.x()).powi(2) + (self.alpha_y * w.y()).powi(2);
+        ((1. + a2 / z2).sqrt() - 1.) / 2.
+    }
+
+    /// Fraction of the microfacets facing `w` that it sees.
+    pub fn g1(&self, w: Vec3) -> f32 {
+        1. / (1. + self.lambda(w))
+    }
+
+    /// Fraction of the microfacets that both `wo` and `wi` see, with the height-correlated form
+    /// that doesn't count the ones hidden from both twice.
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f32 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Density of the normals of the microfacets `wo` sees, which is what
+    /// [`Ggx::sample_visible`] picks `wm` with.
+    pub fn visible_pdf(&self, wo: Vec3, wm: Vec3) -> f32 {
+        if wo.z() <= 0. {
+            return 0.;
+        }
+
+        self.g1(wo) * wo.dot(wm).max(0.) * self.d(wm) / wo.z()
+    }
+
+    /// Normal of a microfacet that `wo`, above the surface, sees, picked by `u` (Heitz, "Sampling
+    /// the GGX Distribution of Visible Normals", 2018). Stretching the distribution to
+    /// roughness 1 turns the visible microfacets into a disk seen from `wo`.
+    pub fn sample_visible(&self, wo: Vec3, u: (f32, f32)) -> Vec3 {
+        let vh = Vec3::new(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()).unit_vector();
+
+        let length_squared = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if length_squared > 0. {
+            Vec3::new(-vh.y(), vh.x(), 0.) / length_squared.sqrt()
+        } else {
+            Vec3::new(1., 0., 0.)
+        };
+        let t2 = vh.cross(t1);
+
+        // A point on the disk, squeezed into the part of it that isn't hidden.
+        let r = u.0.sqrt();
+        let phi = 2. * PI * u.1;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1. + vh.z());
+        let p2 = (1. - s) * (1. - p1 * p1).max(0.).sqrt() + s * r * phi.sin();
+        let nh = t1 * p1 + t2 * p2 + vh * (1. - p1 * p1 - p2 * p2).max(0.).sqrt();
+
+        Vec3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(1e-6),
+        )
+        .unit_vector()
+    }
+}
+
+/// Burley's GTR1 distribution, whose long tails give the haze around the highlights of
+/// clearcoats. Its roughness `alpha` is meaningful up to 1, where it turns uniform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gtr1 {
+    alpha: f32,
+}
+
+impl Gtr1 {
+    pub fn new(alpha: f32) -> Self {
+        Self {
+            alpha: alpha.clamp(1e-3, 0.999),
+        }
+    }
+
+    /// Density of microfacet normals `wm`, per unit of projected area.
+    pub fn d(&self, wm: Vec3) -> f32 {
+        if wm.z() <= 0. {
+            return 0.;
+        }
+
+        let a2 = self.alpha * self.alpha;
+        (a2 - 1.) / (PI * a2.ln() * (1. + (a2 - 1.) * wm.z() * wm.z()))
+    }
+
+    /// Normal picked by `u` with a density of `d(wm) * wm.z()`.
+    pub fn sample(&self, u: (f32, f32)) -> Vec3 {
+        let a2 = self.alpha * self.alpha;
+        let cos2 = ((1. - a2.powf(1. - u.0)) / (1. - a2)).clamp(0., 1.);
+        let sin = (1. - cos2).sqrt();
+        let phi = 2. * PI * u.1;
+
+        Vec3::new(sin * phi.cos(), sin * phi.sin(), cos2.sqrt())
+    }
+}
+
+/// Fraction of light reflected by a conductor with complex index of refraction `eta + ik`, for
+/// light arriving at an angle with a cosine of `cos_theta` (from outside, in a vacuum).
+pub fn fresnel_conductor(cos_theta: f32, eta: Color, k: Color) -> Color {
+    let f = |eta: f32, k: f32| {
+        let cos2 = cos_theta.clamp(0., 1.).powi(2);
+        let sin2 = 1. - cos2;
+        let eta2 = eta * eta;
+        let k2 = k * k;
+
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4. * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.).sqrt();
+        let t2 = 2. * cos_theta.clamp(0., 1.) * a;
+        let rs = (t1 - t2) / (t1 + t2).max(f32::MIN_POSITIVE);
+
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+
+        0.5 * (rp + rs)
+    };
+
+    Color::new(f(eta.x(), k.x()), f(eta.y(), k.y()), f(eta.z(), k.z()))
+}
+
+/// Fraction of light reflected at the boundary between two dielectrics, for light arriving at
+/// an angle with a cosine of `cos_theta` and going into a medium whose index of refraction is
+/// `eta` times the one it comes from. It's 1 past the critical angle, where all of it is.
+pub fn fresnel_dielectric(cos_theta: f32, eta: f32) -> f32 {
+    let cos_i = cos_theta.clamp(0., 1.);
+    let sin2_t = (1. - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1. {
+        return 1.;
+    }
+
+    let cos_t = (1. - sin2_t).sqrt();
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+
+    (parallel * parallel + perpendicular * perpendicular) / 2.
+}
+
+/// Schlick's approximation of the Fresnel reflectance, from the reflectance `f0` at normal
+/// incidence.
+pub fn fresnel_schlick(cos_theta: f32, f0: Color) -> Color {
+    let m = (1. - cos_theta.clamp(0., 1.)).powi(5);
+    f0 + (Color::new(1., 1., 1.) - f0) * m
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fresnel_conductor, fresnel_dielectric, Ggx, Gtr1};
+    use crate::{
+        sampler::uniform_sphere,
+        vec3::{Color, Vec3},
+    };
+    use std::f32::consts::PI;
+
+    use fastrand::Rng;
+
+    #[test]
+    fn visible_normals_match_their_density() {
+        let rng = Rng::with_seed(2);
+        let wo = Vec3::new(0.6, -0.2, 0.5).unit_vector();
+
+        for ggx in [Ggx::from_roughness(0.5, 0.5), Ggx::from_roughness(0.3, 0.8)] {
+            // Projected microfacet area is the surface's, and visible normals integrate to one.
+            let n = 400_000;
+            let (mut projected, mut visible) = (0., 0.);
+            for _ in 0..n {
+                let wm = uniform_sphere((rng.f32(), rng.f32()));
+                projected += ggx.d(wm) * wm.z().max(0.);
+                visible += ggx.visible_pdf(wo, wm);
+            }
+            let scale = 4. * PI / n as f32;
+            assert!(
+                (projected * scale - 1.).abs() < 0.03,
+                "{}",
+                projected * scale
+            );
+            assert!((visible * scale - 1.).abs() < 0.03, "{}", visible * scale);
+
+            // Sampled normals average to the mean of the density, along every axis.
+            let mut expected = Vec3::default();
+            for _ in 0..n {
+                let wm = uniform_sphere((rng.f32(), rng.f32()));
+                expected += wm * (ggx.visible_pdf(wo, wm) * scale);
+            }
+            let mut mean = Vec3::default();
+            for _ in 0..n {
+                let wm = ggx.sample_visible(wo, (rng.f32(), rng.f32()));
+                assert!(wm.z() > 0. && wo.dot(wm) >= -1e-4);
+                mean += wm / n as f32;
+            }
+            assert!((mean - expected).len() < 0.01, "{mean:?} vs {expected:?}");
+        }
+    }
+
+    #[test]
+    fn smooth_along_one_axis() {
+        let rng = Rng::with_seed(6);
+
+        for ggx in [Ggx::from_roughness(0., 0.5), Ggx::from_roughness(0.5, 0.)] {
+            assert!(!ggx.is_smooth());
+
+            for _ in 0..10_000 {
+                let wo = uniform_sphere((rng.f32(), rng.f32()));
+                let wi = uniform_sphere((rng.f32(), rng.f32()));
+                let wm = ggx.sample_visible(wo, (rng.f32(), rng.f32()));
+
+                assert!(wm.x().is_finite() && wm.y().is_finite() && wm.z().is_finite());
+                for value in [ggx.d(wm), ggx.g(wo, wi), ggx.visible_pdf(wo, wm)] {
+                    assert!(value.is_finite(), "{wo:?} {wm:?}: {value}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gtr1_samples_match_its_density() {
+        let rng = Rng::with_seed(5);
+
+        for gtr1 in [Gtr1::new(0.1), Gtr1::new(0.6)] {
+            // Projected microfacet area is the surface's, and sampled normals are as high as
+            // the density says.
+            let n = 400_000;
+            let scale = 4. * PI / n as f32;
+            let (mut projected, mut expected) = (0., 0.);
+            for _ in 0..n {
+                let wm = uniform_sphere((rng.f32(), rng.f32()));
+                projected += gtr1.d(wm) * wm.z().max(0.) * scale;
+                expected += gtr1.d(wm) * wm.z().max(0.) * wm.z() * scale;
+            }
+            let mean = (0..n)
+                .map(|_| gtr1.sample((rng.f32(), rng.f32())).z())
+                .sum::<f32>()
+                / n as f32;
+
+            assert!((projected - 1.).abs() < 0.03, "{projected}");
+            assert!((mean - expected).abs() < 0.01, "{mean} vs {expected}");
+        }
+    }
+
+    #[test]
+    fn conductor_fresnel() {
+        let (eta, k) = (Color::new(0.2, 1.5, 2.), Color::new(3.9, 0., 1.));
+
+        // At normal incidence it's ((eta - 1)² + k²) / ((eta + 1)² + k²).
+        let r = fresnel_conductor(1., eta, k);
+        assert!((r.x() - (0.64 + 15.21) / (1.44 + 15.21)).abs() < 1e-5);
+        assert!((r.y() - 0.04).abs() < 1e-5);
+
+        // Everything is reflected at grazing angles.
+        let grazing = fresnel_conductor(0., eta, k);
+        assert!((grazing - Color::new(1., 1., 1.)).len() < 1e-5);
+    }
+
+    #[test]
+    fn dielectric_fresnel() {
+        assert!((fresnel_dielectric(1., 1.5) - 0.04).abs() < 1e-6);
+        assert!((fresnel_dielectric(1., 1. / 1.5) - 0.04).abs() < 1e-6);
+        assert_eq!(fresnel_dielectric(0.5, 1.), 0.);
+        assert!((fresnel_dielectric(0., 1.5) - 1.).abs() < 1e-6);
+
+        // Past the critical angle, about 41.8° out of glass, everything is reflected.
+        assert!(fresnel_dielectric(40f32.to_radians().cos(), 1. / 1.5) < 1.);
+        assert_eq!(fresnel_dielectric(43f32.to_radians().cos(), 1. / 1.5), 1.);
+    }
+}
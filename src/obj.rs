@@ -0,0 +1,309 @@
+//! Wavefront OBJ loader: positions, normals and texture coordinates (`v`, `vn`, `vt`), polygonal
+//! faces (`f`, fan-triangulated) and groups (`g`, `o`). Other statements are ignored.
+
+use crate::{material::Materials, ray::Point3, triangle::MeshData, vec3::Vec3};
+
+use std::{collections::HashMap, fmt, fs, io, path::Path};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io(e) => write!(f, "{e}"),
+            ObjError::Parse { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl From<io::Error> for ObjError {
+    fn from(e: io::Error) -> Self {
+        ObjError::Io(e)
+    }
+}
+
+/// A named group of faces, with the vertices it references.
+pub struct ObjGroup {
+    pub name: String,
+    pub mesh: MeshData,
+}
+
+pub fn load(path: impl AsRef<Path>, material: Materials) -> Result<Vec<ObjGroup>, ObjError> {
+    parse(&fs::read_to_string(path)?, material)
+}
+
+/// Corner of a face: indices into the position, texture coordinate and normal lists.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+struct Group {
+    name: String,
+    faces: Vec<[Corner; 3]>,
+}
+
+/// Returns the non-empty groups in order of first appearance, faces before the first group
+/// statement belong to `default`. Faces without area don't count.
+pub fn parse(src: &str, material: Materials) -> Result<Vec<ObjGroup>, ObjError> {
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+
+    let mut groups = vec![Group {
+        name: "default".into(),
+        faces: Vec::new(),
+    }];
+    let mut current = 0;
+
+    for (i, raw) in src.lines().enumerate() {
+        let line = i + 1;
+        let err = |message: String| ObjError::Parse { line, message };
+
+        let raw = raw.split('#').next().unwrap();
+        let mut words = raw.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+
+        let mut floats = |n: usize| -> Result<Vec<f32>, ObjError> {
+            let values = words
+                .by_ref()
+                .take(n)
+                .map(|w| w.parse().map_err(|_| err(format!("invalid number `{w}`"))))
+                .collect::<Result<Vec<f32>, _>>()?;
+
+            if values.len() < n {
+                return Err(err(format!("`{keyword}` needs {n} numbers")));
+            }
+            Ok(values)
+        };
+
+        match keyword {
+            "v" => {
+                let v = floats(3)?;
+                positions.push(Point3::new(v[0], v[1], v[2]));
+            }
+            "vn" => {
+                let v = floats(3)?;
+                normals.push(Vec3::new(v[0], v[1], v[2]).unit_vector());
+            }
+            "vt" => {
+                let v = floats(2)?;
+                uvs.push((v[0], v[1]));
+            }
+            "g" | "o" => {
+                let name = words.collect::<Vec<_>>().join(" ");
+                let name = if name.is_empty() {
+                    "default".to_owned()
+                } else {
+                    name
+                };
+
+                current = match groups.iter().position(|g| g.name == name) {
+                    Some(index) => index,
+                    None => {
+                        groups.push(Group {
+                            name,
+                            faces: Vec::new(),
+                        });
+                        groups.len() - 1
+                    }
+                };
+            }
+            "f" => {
+                let corners = words
+                    .map(|w| corner(w, positions.len(), uvs.len(), normals.len()).map_err(&err))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                if corners.len() < 3 {
+                    return Err(err("a face needs at least 3 vertices".into()));
+                }
+
+                // Triangles without area, like those of collinear vertices, can't be hit or
+                // sampled and have no normal, so they are left out.
+                let faces = &mut groups[current].faces;
+                for i in 1..corners.len() - 1 {
+                    let face = [corners[0], corners[i], corners[i + 1]];
+                    let [a, b, c] = face.map(|(v, _, _)| positions[v]);
+                    if (b - a).cross(c - a).len_squared() > 0. {
+                        faces.push(face);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(groups
+        .into_iter()
+        .filter(|g| !g.faces.is_empty())
+        .map(|g| ObjGroup {
+            mesh: build_mesh(&g.faces, &positions, &uvs, &normals, material.clone()),
+            name: g.name,
+        })
+        .collect())
+}
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, with 1-based or negative (relative) indices.
+fn corner(word: &str, positions: usize, uvs: usize, normals: usize) -> Result<Corner, String> {
+    let index = |s: &str, len: usize, what: &str| -> Result<usize, String> {
+        let i: i64 = s
+            .parse()
+            .map_err(|_| format!("invalid {what} index `{s}`"))?;
+
+        let resolved = if i < 0 { len as i64 + i } else { i - 1 };
+        if i == 0 || resolved < 0 || resolved >= len as i64 {
+            return Err(format!("{what} index {i} out of range"));
+        }
+        Ok(resolved as usize)
+    };
+
+    let mut parts = word.split('/');
+    let v = index(parts.next().unwrap(), positions, "vertex")?;
+    let vt = match parts.next() {
+        None | Some("") => None,
+        Some(s) => Some(index(s, uvs, "texture coordinate")?),
+    };
+    let vn = match parts.next() {
+        None | Some("") => None,
+        Some(s) => Some(index(s, normals, "normal")?),
+    };
+
+    Ok((v, vt, vn))
+}
+
+/// Gives every distinct corner its own vertex. Normals and texture coordinates are only kept
+/// when every corner of the group has them.
+fn build_mesh(
+    faces: &[[Corner; 3]],
+    positions: &[Point3],
+    uvs: &[(f32, f32)],
+    normals: &[Vec3],
+    material: Materials,
+) -> MeshData {
+    let all_uvs = faces.iter().flatten().all(|c| c.1.is_some());
+    let all_normals = faces.iter().flatten().all(|c| c.2.is_some());
+
+    let mut mesh = MeshData {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        uvs: Vec::new(),
+        indices: Vec::with_capacity(faces.len()),
+        material,
+    };
+    let mut vertices = HashMap::new();
+
+    for face in faces {
+        let triangle = face.map(|(v, vt, vn)| {
+            let key = (v, vt.filter(|_| all_uvs), vn.filter(|_| all_normals));
+
+            *vertices.entry(key).or_insert_with(|| {
+                mesh.positions.push(positions[v]);
+                if let Some(vt) = key.1 {
+                    mesh.uvs.push(uvs[vt]);
+                }
+                if let Some(vn) = key.2 {
+                    mesh.normals.push(normals[vn]);
+                }
+                mesh.positions.len() as u32 - 1
+            })
+        });
+
+        mesh.indices.push(triangle);
+    }
+
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, ObjError};
+    use crate::material::{Lambertian, Materials};
+
+    fn material() -> Materials {
+        Materials::Lambertian(Lambertian::default())
+    }
+
+    #[test]
+    fn groups_and_triangulation() {
+        let groups = parse(
+            "
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vt 0 1
+            vn 0 0 2
+            f 1/1/1 2/2/1 3/3/1 4/4/1  # a quad
+            g top
+            v 0 0 1
+            f -4 -3 -1
+            f 1//1 3 5
+            ",
+            material(),
+        )
+        .unwrap();
+
+        assert_eq!(groups.len(), 2);
+
+        let quad = &groups[0];
+        assert_eq!(quad.name, "default");
+        assert_eq!(quad.mesh.indices, [[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(quad.mesh.uvs.len(), 4);
+        assert_eq!(quad.mesh.normals.len(), 4);
+        assert!((quad.mesh.normals[0].len() - 1.).abs() < 1e-6);
+
+        // Not every corner has a normal, so the group is flat shaded.
+        let top = &groups[1];
+        assert_eq!(top.name, "top");
+        assert_eq!(top.mesh.indices, [[0, 1, 2], [3, 1, 2]]);
+        assert_eq!(top.mesh.positions.len(), 4);
+        assert!(top.mesh.normals.is_empty());
+        assert!(top.mesh.uvs.is_empty());
+    }
+
+    #[test]
+    fn degenerate_faces() {
+        let src = "
+            v 0 0 0
+            v 1 0 0
+            v 2 0 0
+            v 0 1 0
+            f 1 2 3
+            f 1 2 2
+            g flat
+            f 1 2 3 4
+            ";
+        let groups = parse(src, material()).unwrap();
+
+        // Only the second half of the quad has any area.
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].name, "flat");
+        assert_eq!(groups[0].mesh.indices, [[0, 1, 2]]);
+        assert_eq!(groups[0].mesh.positions.len(), 3);
+
+        assert!(parse("v 0 0 0\nv 1 1 1\nv 2 2 2\nf 1 2 3\n", material())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        let line = |src| match parse(src, material()) {
+            Err(ObjError::Parse { line, .. }) => line,
+            _ => panic!("expected a parse error"),
+        };
+
+        assert_eq!(line("v 0 0 0\nv 1 0\n"), 2);
+        assert_eq!(line("v 0 0 0\nf 1 2 3\n"), 2);
+        assert_eq!(line("v 0 0 0\nv 1 0 0\nf 1 2\n"), 3);
+        assert_eq!(line("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2 3\n"), 4);
+    }
+}
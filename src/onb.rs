@@ -0,0 +1,94 @@
+use crate::vec3::Vec3;
+
+/// Orthonormal basis, with `w` along a given direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    /// Branchless construction from Duff et al., "Building an Orthonormal Basis, Revisited".
+    pub fn from_w(n: Vec3) -> Self {
+        let w = n.unit_vector();
+        let sign = 1f32.copysign(w.z());
+        let a = -1. / (sign + w.z());
+        let b = w.x() * w.y() * a;
+
+        Self {
+            u: Vec3::new(1. + sign * w.x() * w.x() * a, sign * b, -sign * w.x()),
+            v: Vec3::new(b, sign + w.y() * w.y() * a, -w.y()),
+            w,
+        }
+    }
+
+    /// Basis with `w` along `n` and `u` along the part of `tangent` perpendicular to it, so that
+    /// anisotropic materials line up with the surface's parameterisation. Any tangent does when
+    /// that part is too short.
+    pub fn from_w_and_u(n: Vec3, tangent: Vec3) -> Self {
+        let w = n.unit_vector();
+        let u = tangent - w * w.dot(tangent);
+        if u.len_squared() <= 1e-12 * tangent.len_squared() {
+            return Self::from_w(w);
+        }
+
+        let u = u.unit_vector();
+        Self {
+            u,
+            v: w.cross(u),
+            w,
+        }
+    }
+
+    /// Transforms from the basis to world coordinates.
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        self.u * a.x() + self.v * a.y() + self.w * a.z()
+    }
+
+    /// Transforms from world coordinates to the basis.
+    pub fn to_local(self, a: Vec3) -> Vec3 {
+        Vec3::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Onb;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn orthonormal() {
+        for n in [
+            Vec3::new(0., 0., 1.),
+            Vec3::new(0., 0., -1.),
+            Vec3::new(1., 2., 3.),
+            Vec3::new(-0.3, 0.1, -5.),
+        ] {
+            let b = Onb::from_w(n);
+
+            for (a, c) in [(b.u, b.v), (b.v, b.w), (b.w, b.u)] {
+                assert!(a.dot(c).abs() < 1e-6);
+                assert!((a.len() - 1.).abs() < 1e-6);
+            }
+            assert!((b.u.cross(b.v) - b.w).len() < 1e-6);
+            assert!((b.local(Vec3::new(0., 0., 1.)) - n.unit_vector()).len() < 1e-6);
+        }
+
+        let b = Onb::from_w_and_u(Vec3::new(0., 2., 0.), Vec3::new(3., 1., 0.));
+        assert_eq!(
+            (b.u, b.v, b.w),
+            (
+                Vec3::new(1., 0., 0.),
+                Vec3::new(0., 0., -1.),
+                Vec3::new(0., 1., 0.)
+            )
+        );
+        let a = Vec3::new(0.3, -2., 1.);
+        assert!((b.local(b.to_local(a)) - a).len() < 1e-6);
+        assert_eq!(
+            Onb::from_w_and_u(Vec3::new(0., 0., 1.), Vec3::default()),
+            Onb::from_w(Vec3::new(0., 0., 1.))
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Image writers. The renderer hands over a linear, unclamped framebuffer and each format
+//! converts it to what it stores.
+
+use crate::{hdr, png, vec3::Color};
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Binary `P6` PPM.
+    Ppm,
+    /// ASCII `P3` PPM.
+    PpmAscii,
+    Png,
+    /// Radiance RGBE.
+    Hdr,
+    /// Portable float map.
+    Pfm,
+    /// Uncompressed scanline OpenEXR.
+    Exr,
+}
+
+impl Format {
+    pub const NAMES: &'static str = "`ppm`, `ppm-ascii`, `png`, `hdr`, `pfm` or `exr`";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ppm" => Some(Format::Ppm),
+            "ppm-ascii" => Some(Format::PpmAscii),
+            "png" => Some(Format::Png),
+            "hdr" => Some(Format::Hdr),
+            "pfm" => Some(Format::Pfm),
+            "exr" => Some(Format::Exr),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format from the file extension, `.ppm` files are binary.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        Self::from_name(&ext).filter(|f| *f != Format::PpmAscii)
+    }
+}
+
+/// Writes `pixels`, in rows from top to bottom, to `path`.
+pub fn write_image(
+    path: &Path,
+    format: Format,
+    width: u32,
+    height: u32,
+    pixels: &[Color],
+) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+
+    match format {
+        Format::Ppm => {
+            write!(w, "P6\n{width} {height}\n255\n")?;
+            w.write_all(&to_srgb8(pixels))?;
+        }
+        Format::PpmAscii => {
+            write!(w, "P3\n{width} {height}\n255\n")?;
+            for rgb in to_srgb8(pixels).chunks(3) {
+                writeln!(w, "{} {} {}", rgb[0], rgb[1], rgb[2])?;
+            }
+        }
+        Format::Png => png::write(&mut w, width, height, &to_srgb8(pixels))?,
+        Format::Hdr => hdr::write_radiance(&mut w, width, height, pixels)?,
+        Format::Pfm => hdr::write_pfm(&mut w, width, height, pixels)?,
+        Format::Exr => hdr::write_exr(&mut w, width, height, pixels)?,
+    }
+
+    w.flush()
+}
+
+/// Encodes linear radiance with the sRGB transfer function, clamping to [0, 1].
+pub fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.clamp(0., 1.);
+
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}
+
+fn to_srgb8(pixels: &[Color]) -> Vec<u8> {
+    pixels
+        .iter()
+        .flat_map(|p| [p.x(), p.y(), p.z()])
+        .map(|c| (linear_to_srgb(c) * 255. + 0.5) as u8)
+        .collect()
+}
@@ -0,0 +1,94 @@
+//! Perlin gradient noise, as in _Ray Tracing: The Next Week_.
+
+use crate::{ray::Point3, vec3::Vec3};
+
+const POINT_COUNT: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    /// The same seed always gives the same noise.
+    pub fn new(seed: u64) -> Self {
+        let rng = fastrand::Rng::with_seed(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| {
+                Vec3::new(
+                    rng.f32() * 2. - 1.,
+                    rng.f32() * 2. - 1.,
+                    rng.f32() * 2. - 1.,
+                )
+                .unit_vector()
+            })
+            .collect();
+
+        let permutation = || {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            rng.shuffle(&mut p);
+            p
+        };
+
+        Self {
+            gradients,
+            perm_x: permutation(),
+            perm_y: permutation(),
+            perm_z: permutation(),
+        }
+    }
+
+    /// Smooth noise in [-1, 1], trilinearly interpolating the gradients at the lattice corners.
+    pub fn noise(&self, p: Point3) -> f32 {
+        let (fx, fy, fz) = (p.x().floor(), p.y().floor(), p.z().floor());
+        let (u, v, w) = (p.x() - fx, p.y() - fy, p.z() - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+        // Hermite smoothing hides the lattice.
+        let (uu, vv, ww) = (
+            u * u * (3. - 2. * u),
+            v * v * (3. - 2. * v),
+            w * w * (3. - 2. * w),
+        );
+
+        let mut accum = 0.;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let wrap = |n: i64| (n & (POINT_COUNT as i64 - 1)) as usize;
+                    let gradient = self.gradients[self.perm_x[wrap(i + di)]
+                        ^ self.perm_y[wrap(j + dj)]
+                        ^ self.perm_z[wrap(k + dk)]];
+
+                    let (di, dj, dk) = (di as f32, dj as f32, dk as f32);
+                    let weight = Vec3::new(u - di, v - dj, w - dk);
+
+                    accum += (di * uu + (1. - di) * (1. - uu))
+                        * (dj * vv + (1. - dj) * (1. - vv))
+                        * (dk * ww + (1. - dk) * (1. - ww))
+                        * gradient.dot(weight);
+                }
+            }
+        }
+
+        accum
+    }
+
+    /// Sum of `depth` octaves of noise, each at twice the frequency and half the weight.
+    pub fn turbulence(&self, p: Point3, depth: u32) -> f32 {
+        let mut accum = 0.;
+        let mut p = p;
+        let mut weight = 1.;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(p);
+            weight *= 0.5;
+            p = p * 2.;
+        }
+
+        accum.abs()
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Materials,
+    onb::Onb,
+    ray::{Point3, Ray},
+    vec3::Vec3,
+};
+
+/// Infinite plane through `point`, with its front face towards `normal`. Being unbounded it
+/// can't be sampled as a light, but it still lights the scene when paths hit it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plane {
+    point: Point3,
+    /// `w` is the unit normal, `u` and `v` span the plane.
+    frame: Onb,
+    material: Materials,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, m: Materials) -> Self {
+        Self {
+            point,
+            frame: Onb::from_w(normal),
+            material: m,
+        }
+    }
+
+    pub fn material(&self) -> &Materials {
+        &self.material
+    }
+}
+
+impl Hittable for Plane {
+    /// `u` and `v` of the hit are its coordinates in the plane, in scene units from `point`, so
+    /// textures repeat over it.
+    fn hit_surface(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let denom = self.frame.w.dot(r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = self.frame.w.dot(self.point - r.origin) / denom;
+        if !(t_min..=t_max).contains(&t) {
+            return None;
+        }
+
+        let point = r.at(t);
+        let planar = point - self.point;
+
+        let mut hr = HitRecord::new(point, self.frame.w, t, &self.material);
+        hr.set_face_normal(r);
+        (hr.u, hr.v) = (planar.dot(self.frame.u), planar.dot(self.frame.v));
+        (hr.dpdu, hr.dpdv) = (self.frame.u, self.frame.v);
+
+        Some(hr)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Plane;
+    use crate::{
+        hittable::Hittable,
+        material::{Lambertian, Materials},
+        ray::{Point3, Ray},
+        vec3::Vec3,
+    };
+
+    #[test]
+    fn hits_from_both_sides() {
+        let plane = Plane::new(
+            Point3::new(0., 1., 0.),
+            Vec3::new(0., 2., 0.),
+            Materials::Lambertian(Lambertian::default()),
+        );
+
+        let above = Ray::new(Point3::new(3., 5., -2.), Vec3::new(1., -2., 0.));
+        let hit = plane.hit_surface(&above, 0.001, f32::INFINITY).unwrap();
+        assert!(hit.front_face);
+        assert_eq!(hit.normal, Vec3::new(0., 1., 0.));
+        assert!((hit.t - 2.).abs() < 1e-6);
+
+        // Surface coordinates are distances along the tangents.
+        let planar = hit.point - Point3::new(0., 1., 0.);
+        assert!((hit.dpdu * hit.u + hit.dpdv * hit.v - planar).len() < 1e-5);
+
+        let below = Ray::new(Point3::new(0., -1., 0.), Vec3::new(0.3, 1., 0.2));
+        let hit = plane.hit_surface(&below, 0.001, f32::INFINITY).unwrap();
+        assert!(!hit.front_face);
+        assert_eq!(hit.normal, Vec3::new(0., -1., 0.));
+
+        let parallel = Ray::new(Point3::new(0., 2., 0.), Vec3::new(1., 0., 1.));
+        assert!(plane.hit_surface(&parallel, 0.001, f32::INFINITY).is_none());
+    }
+}
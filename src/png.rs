@@ -0,0 +1,736 @@
+//! PNG encoder for 8-bit RGB images, and a decoder for textures.
+//!
+//! Scanlines are filtered with the per-row heuristic from the PNG specification and compressed
+//! with a small deflate implementation: greedy LZ77 matching over a hash table, coded with the
+//! fixed Huffman tables. The decoder reads every non-interlaced color type and bit depth.
+
+use std::io::{self, Write};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Writes `rgb`, `width * height` pixels of 3 bytes each, as an sRGB PNG.
+pub fn write(w: &mut impl Write, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    assert_eq!(rgb.len(), width as usize * height as usize * 3);
+
+    w.write_all(&SIGNATURE)?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, color type 2 (RGB), deflate, adaptive filtering, no interlace.
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(w, b"IHDR", &ihdr)?;
+
+    // Perceptual rendering intent.
+    write_chunk(w, b"sRGB", &[0])?;
+
+    write_chunk(w, b"IDAT", &zlib_compress(&filter(width as usize, rgb)))?;
+    write_chunk(w, b"IEND", &[])
+}
+
+fn write_chunk(w: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+
+    let crc = crc32_update(crc32_update(!0, kind), data);
+    w.write_all(&(!crc).to_be_bytes())
+}
+
+/// Updates a CRC-32 (ISO 3309, as used by PNG), callers start from `!0` and invert the result.
+pub fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut n = 0;
+        while n < 256 {
+            let mut c = n as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 == 1 {
+                    0xedb8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+                k += 1;
+            }
+            table[n] = c;
+            n += 1;
+        }
+        table
+    };
+
+    for &b in data {
+        crc = TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1, 0);
+
+    // 5552 is the largest block that can't overflow `b` before the reduction.
+    for block in data.chunks(5552) {
+        for &byte in block {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+
+    (b << 16) | a
+}
+
+/// Prefixes each scanline with the filter type that minimizes the sum of absolute differences.
+fn filter(width: usize, rgb: &[u8]) -> Vec<u8> {
+    const BPP: usize = 3;
+    let stride = width * BPP;
+    let mut out = Vec::with_capacity(rgb.len() + rgb.len() / stride.max(1));
+    let zero = vec![0; stride];
+    let mut candidate = vec![0; stride];
+    let mut best = vec![0; stride];
+
+    for (y, row) in rgb.chunks(stride).enumerate() {
+        let prev = if y == 0 {
+            &zero[..]
+        } else {
+            &rgb[(y - 1) * stride..y * stride]
+        };
+
+        let mut best_type = 0;
+        let mut best_score = u64::MAX;
+
+        for filter_type in 0..5u8 {
+            for i in 0..stride {
+                let a = if i >= BPP { row[i - BPP] } else { 0 };
+                let b = prev[i];
+                let c = if i >= BPP { prev[i - BPP] } else { 0 };
+
+                let predictor = match filter_type {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    _ => paeth(a, b, c),
+                };
+                candidate[i] = row[i].wrapping_sub(predictor);
+            }
+
+            // Treat the residuals as signed, small magnitudes compress best.
+            let score = candidate
+                .iter()
+                .map(|&v| (v as i8).unsigned_abs() as u64)
+                .sum();
+            if score < best_score {
+                best_score = score;
+                best_type = filter_type;
+                std::mem::swap(&mut best, &mut candidate);
+            }
+        }
+
+        out.push(best_type);
+        out.extend_from_slice(&best);
+    }
+
+    out
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    /// Appends the `count` low bits of `value`, least significant first.
+    fn write(&mut self, value: u32, count: u32) {
+        self.acc |= (value as u64) << self.bits;
+        self.bits += count;
+
+        while self.bits >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    /// Huffman codes are packed starting from their most significant bit.
+    fn write_code(&mut self, code: u32, len: u32) {
+        self.write(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+
+/// Symbol `sym` (0..=287) of the fixed literal/length Huffman code.
+fn write_literal_length(w: &mut BitWriter, sym: u32) {
+    match sym {
+        0..=143 => w.write_code(0x30 + sym, 8),
+        144..=255 => w.write_code(0x190 + sym - 144, 9),
+        256..=279 => w.write_code(sym - 256, 7),
+        _ => w.write_code(0xc0 + sym - 280, 8),
+    }
+}
+
+fn write_match(w: &mut BitWriter, len: usize, dist: usize) {
+    let i = LENGTH_BASE.partition_point(|&b| b as usize <= len) - 1;
+    write_literal_length(w, 257 + i as u32);
+    w.write(
+        (len - LENGTH_BASE[i] as usize) as u32,
+        LENGTH_EXTRA[i] as u32,
+    );
+
+    let i = DISTANCE_BASE.partition_point(|&b| b as usize <= dist) - 1;
+    w.write_code(i as u32, 5);
+    w.write(
+        (dist - DISTANCE_BASE[i] as usize) as u32,
+        DISTANCE_EXTRA[i] as u32,
+    );
+}
+
+/// Raw deflate stream (RFC 1951) in a single fixed-Huffman block.
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut w = BitWriter {
+        out: Vec::with_capacity(data.len() / 2),
+        acc: 0,
+        bits: 0,
+    };
+
+    // Final block, fixed Huffman codes.
+    w.write(1, 1);
+    w.write(1, 2);
+
+    let hash = |i: usize| {
+        let v = u32::from_le_bytes([data[i], data[i + 1], data[i + 2], 0]);
+        (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+    };
+    // Most recent position + 1 of each hashed 3-byte prefix, 0 when empty.
+    let mut head = vec![0usize; 1 << HASH_BITS];
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            let candidate = head[h];
+            head[h] = i + 1;
+
+            if candidate > 0 && i - (candidate - 1) <= WINDOW_SIZE {
+                let start = candidate - 1;
+                let max = (data.len() - i).min(MAX_MATCH);
+                let len = data[start..start + max]
+                    .iter()
+                    .zip(&data[i..i + max])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+
+                if len >= MIN_MATCH {
+                    best = (len, i - start);
+                }
+            }
+        }
+
+        if best.0 > 0 {
+            write_match(&mut w, best.0, best.1);
+
+            // Index the positions skipped over so later matches can find them.
+            for j in i + 1..(i + best.0).min(data.len().saturating_sub(MIN_MATCH - 1)) {
+                head[hash(j)] = j + 1;
+            }
+            i += best.0;
+        } else {
+            write_literal_length(&mut w, data[i] as u32);
+            i += 1;
+        }
+    }
+
+    write_literal_length(&mut w, 256);
+    w.finish()
+}
+
+/// zlib stream (RFC 1950) wrapping [`deflate`].
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    // 32K window, deflate; the check bits make the header a multiple of 31.
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// Decoded image, RGB samples scaled to [0, 1] in rows from top to bottom. Alpha is dropped.
+pub struct Decoded {
+    pub width: u32,
+    pub height: u32,
+    pub rgb: Vec<f32>,
+}
+
+/// Decodes a PNG file. The samples are returned as stored, without any gamma conversion.
+pub fn read(data: &[u8]) -> Result<Decoded, String> {
+    if data.get(..8) != Some(&SIGNATURE[..]) {
+        return Err("not a PNG file".into());
+    }
+
+    let mut header = None;
+    let mut palette = &[][..];
+    let mut idat = Vec::new();
+
+    let mut rest = &data[8..];
+    loop {
+        if rest.len() < 12 {
+            return Err("truncated file".into());
+        }
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        if rest.len() < 12 + len {
+            return Err("truncated chunk".into());
+        }
+        let kind = &rest[4..8];
+        let body = &rest[8..8 + len];
+        let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+        if !crc32_update(crc32_update(!0, kind), body) != crc {
+            return Err(format!(
+                "bad CRC in {} chunk",
+                String::from_utf8_lossy(kind)
+            ));
+        }
+        rest = &rest[12 + len..];
+
+        match kind {
+            b"IHDR" if body.len() == 13 => header = Some(body),
+            b"PLTE" => palette = body,
+            b"IDAT" => idat.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+
+    let header = header.ok_or("missing IHDR chunk")?;
+    let width = u32::from_be_bytes(header[..4].try_into().unwrap());
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    if width == 0 || height == 0 {
+        return Err("empty image".into());
+    }
+    let (depth, color_type, interlace) = (header[8], header[9], header[12]);
+
+    let channels = match (color_type, depth) {
+        (0, 1 | 2 | 4 | 8 | 16) => 1,
+        (2, 8 | 16) => 3,
+        (3, 1 | 2 | 4 | 8) => 1,
+        (4, 8 | 16) => 2,
+        (6, 8 | 16) => 4,
+        _ => {
+            return Err(format!(
+                "invalid color type {color_type} with bit depth {depth}"
+            ))
+        }
+    };
+    if interlace != 0 {
+        return Err("interlaced images aren't supported".into());
+    }
+
+    let bits_per_pixel = channels * depth as usize;
+    let stride = (width as usize * bits_per_pixel).div_ceil(8);
+    let raw = zlib_decompress(&idat)?;
+    if raw.len() < (stride + 1) * height as usize {
+        return Err("not enough image data".into());
+    }
+    let pixels = unfilter(&raw, stride, height as usize, bits_per_pixel.div_ceil(8))?;
+
+    let max = ((1u32 << depth) - 1) as f32;
+    let sample = |row: &[u8], i: usize| -> u32 {
+        match depth {
+            16 => u16::from_be_bytes([row[2 * i], row[2 * i + 1]]) as u32,
+            8 => row[i] as u32,
+            _ => {
+                let bit = i * depth as usize;
+                ((row[bit / 8] >> (8 - depth as usize - bit % 8)) & ((1 << depth) - 1)) as u32
+            }
+        }
+    };
+
+    let mut rgb = Vec::with_capacity(width as usize * height as usize * 3);
+    for row in pixels.chunks(stride) {
+        for x in 0..width as usize {
+            let i = x * channels;
+            match color_type {
+                3 => {
+                    let entry = sample(row, i) as usize * 3;
+                    let color = palette
+                        .get(entry..entry + 3)
+                        .ok_or("palette index out of range")?;
+                    rgb.extend(color.iter().map(|&c| c as f32 / 255.));
+                }
+                0 | 4 => {
+                    let gray = sample(row, i) as f32 / max;
+                    rgb.extend([gray; 3]);
+                }
+                _ => rgb.extend((i..i + 3).map(|i| sample(row, i) as f32 / max)),
+            }
+        }
+    }
+
+    Ok(Decoded { width, height, rgb })
+}
+
+/// Reverses the scanline filters, `bpp` is the number of bytes per complete pixel (at least 1).
+fn unfilter(raw: &[u8], stride: usize, height: usize, bpp: usize) -> Result<Vec<u8>, String> {
+    let mut out = vec![0; stride * height];
+
+    for y in 0..height {
+        let filter_type = raw[y * (stride + 1)];
+        let line = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        let (done, current) = out.split_at_mut(y * stride);
+        let prev = if y == 0 {
+            None
+        } else {
+            Some(&done[(y - 1) * stride..])
+        };
+        let current = &mut current[..stride];
+
+        for i in 0..stride {
+            let a = if i >= bpp { current[i - bpp] } else { 0 };
+            let b = prev.map_or(0, |p| p[i]);
+            let c = if i >= bpp {
+                prev.map_or(0, |p| p[i - bpp])
+            } else {
+                0
+            };
+
+            let predictor = match filter_type {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(format!("invalid filter type {filter_type}")),
+            };
+            current[i] = line[i].wrapping_add(predictor);
+        }
+    }
+
+    Ok(out)
+}
+
+/// Reads bits least significant first, as deflate packs them.
+struct BitReader<'a> {
+    data: &'a [u8],
+    /// Position in bits.
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn bit(&mut self) -> Result<u32, String> {
+        let byte = self
+            .data
+            .get(self.pos / 8)
+            .ok_or("unexpected end of compressed data")?;
+        let bit = (byte >> (self.pos % 8)) & 1;
+        self.pos += 1;
+        Ok(bit as u32)
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            value |= self.bit()? << i;
+        }
+        Ok(value)
+    }
+}
+
+/// Canonical Huffman code, decoded one bit at a time.
+struct Huffman {
+    /// Number of codes of each length.
+    counts: [u16; 16],
+    /// Symbols ordered by code.
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16)
+            .filter(|&s| lengths[s as usize] > 0)
+            .collect();
+        symbols.sort_by_key(|&s| lengths[s as usize]);
+
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, r: &mut BitReader) -> Result<u16, String> {
+        // Codes of each length are consecutive, starting at `first`.
+        let (mut code, mut first, mut index) = (0, 0, 0);
+
+        for &count in &self.counts[1..] {
+            code |= r.bit()? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err("invalid Huffman code".into())
+    }
+}
+
+/// Decompresses a raw deflate stream.
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut r = BitReader { data, pos: 0 };
+    let mut out = Vec::new();
+
+    loop {
+        let last = r.bit()? == 1;
+
+        match r.bits(2)? {
+            0 => {
+                let start = r.pos.div_ceil(8);
+                let header = data.get(start..start + 4).ok_or("truncated stored block")?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                if len as u16 != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err("corrupt stored block length".into());
+                }
+                let block = data
+                    .get(start + 4..start + 4 + len)
+                    .ok_or("truncated stored block")?;
+                out.extend_from_slice(block);
+                r.pos = (start + 4 + len) * 8;
+            }
+            1 => {
+                let mut lengths = [8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                inflate_block(
+                    &mut r,
+                    &mut out,
+                    &Huffman::new(&lengths),
+                    &Huffman::new(&[5; 30]),
+                )?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_tables(&mut r)?;
+                inflate_block(&mut r, &mut out, &literals, &distances)?;
+            }
+            _ => return Err("invalid block type".into()),
+        }
+
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn dynamic_tables(r: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    const ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+
+    let literals = r.bits(5)? as usize + 257;
+    let distances = r.bits(5)? as usize + 1;
+    let code_lengths = r.bits(4)? as usize + 4;
+
+    let mut lengths = [0; 19];
+    for &i in &ORDER[..code_lengths] {
+        lengths[i] = r.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&lengths);
+
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (value, repeat) = match code_length_code.decode(r)? {
+            len @ 0..=15 => (len as u8, 1),
+            16 => {
+                let prev = *lengths.last().ok_or("repeat with no previous length")?;
+                (prev, 3 + r.bits(2)?)
+            }
+            17 => (0, 3 + r.bits(3)?),
+            _ => (0, 11 + r.bits(7)?),
+        };
+        lengths.resize(lengths.len() + repeat as usize, value);
+    }
+    if lengths.len() > literals + distances {
+        return Err("code lengths overflow".into());
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literals]),
+        Huffman::new(&lengths[literals..]),
+    ))
+}
+
+fn inflate_block(
+    r: &mut BitReader,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let sym = literals.decode(r)? as usize;
+
+        match sym {
+            0..=255 => out.push(sym as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let i = sym - 257;
+                let len = LENGTH_BASE[i] as usize + r.bits(LENGTH_EXTRA[i] as u32)? as usize;
+
+                let i = distances.decode(r)? as usize;
+                if i >= 30 {
+                    return Err("invalid distance code".into());
+                }
+                let dist = DISTANCE_BASE[i] as usize + r.bits(DISTANCE_EXTRA[i] as u32)? as usize;
+                if dist > out.len() {
+                    return Err("distance too far back".into());
+                }
+
+                // Byte by byte, the match may overlap what it produces.
+                let start = out.len() - dist;
+                for j in 0..len {
+                    out.push(out[start + j]);
+                }
+            }
+            _ => return Err("invalid literal/length code".into()),
+        }
+    }
+}
+
+/// Decompresses a zlib stream and checks its Adler-32.
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 6
+        || data[0] & 0x0f != 8
+        || !u16::from_be_bytes([data[0], data[1]]).is_multiple_of(31)
+    {
+        return Err("invalid zlib header".into());
+    }
+    if data[1] & 0x20 != 0 {
+        return Err("preset dictionaries aren't supported".into());
+    }
+
+    let out = inflate(&data[2..])?;
+    let checksum = u32::from_be_bytes(data[data.len() - 4..].try_into().unwrap());
+    if adler32(&out) != checksum {
+        return Err("zlib checksum mismatch".into());
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{adler32, crc32_update, read, write, zlib_compress, zlib_decompress};
+
+    #[test]
+    fn checksums() {
+        assert_eq!(!crc32_update(!0, b"IEND"), 0xae42_6082);
+        assert_eq!(!crc32_update(!0, b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(adler32(&[0xff; 10_000]), 0xb623_eb2b);
+    }
+
+    #[test]
+    fn round_trip() {
+        let (width, height) = (37, 11);
+        let rgb: Vec<u8> = (0..width * height * 3)
+            .map(|i| (((i * 7) % 251) ^ (i / 97)) as u8)
+            .collect();
+
+        let mut file = Vec::new();
+        write(&mut file, width as u32, height as u32, &rgb).unwrap();
+        let decoded = read(&file).unwrap();
+
+        assert_eq!((decoded.width, decoded.height), (37, 11));
+        let bytes: Vec<u8> = decoded
+            .rgb
+            .iter()
+            .map(|&c| (c * 255.).round() as u8)
+            .collect();
+        assert_eq!(bytes, rgb);
+
+        let data = b"abcabcabcabcabc hello hello hello".repeat(100);
+        assert_eq!(zlib_decompress(&zlib_compress(&data)).unwrap(), data);
+    }
+
+    #[test]
+    fn empty_header() {
+        let mut file = Vec::new();
+        write(&mut file, 1, 1, &[0, 0, 0]).unwrap();
+
+        // IHDR's body follows the signature and the chunk's length and type, with its CRC after.
+        file[16..20].copy_from_slice(&0u32.to_be_bytes());
+        let crc = !crc32_update(crc32_update(!0, b"IHDR"), &file[16..29]);
+        file[29..33].copy_from_slice(&crc.to_be_bytes());
+
+        assert_eq!(read(&file).err().as_deref(), Some("empty image"));
+    }
+
+    #[test]
+    fn dynamic_and_stored_blocks() {
+        // A dynamic Huffman block, from Python's `zlib.compress(data, 9)`.
+        let dynamic = [
+            0x78, 0xda, 0x05, 0xc1, 0x89, 0x09, 0xc0, 0x20, 0x0c, 0x00, 0xc0, 0xd9, 0x82, 0x14,
+            0x85, 0x50, 0x25, 0x96, 0x40, 0xe2, 0x43, 0xf0, 0xa9, 0xdd, 0x7f, 0x83, 0xde, 0x41,
+            0xe8, 0x3a, 0x29, 0x15, 0x44, 0xc1, 0xbb, 0x11, 0x9f, 0xbe, 0xa2, 0x4f, 0xdb, 0x40,
+            0x78, 0x64, 0xb2, 0xfc, 0x2c, 0xa9, 0xd7, 0x06, 0x8e, 0xec, 0x8e, 0xb7, 0xf6, 0xa9,
+            0xbc, 0x5a, 0xc0, 0x26, 0xba, 0x50, 0x7f, 0x5c, 0xec, 0x13, 0xed,
+        ];
+        let data: Vec<u8> = (0..60u32)
+            .map(|i| ((i * i * 7 + i / 3) % 40 + 65) as u8)
+            .collect();
+        assert_eq!(zlib_decompress(&dynamic).unwrap(), data);
+
+        // A single stored block holding "abc".
+        let stored = [
+            0x78, 0x01, 0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c', 0x02, 0x4d, 0x01, 0x27,
+        ];
+        assert_eq!(zlib_decompress(&stored).unwrap(), b"abc");
+    }
+}
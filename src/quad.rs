@@ -0,0 +1,300 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Materials,
+    ray::{Point3, Ray},
+    sampler::ONE_MINUS_EPSILON,
+    vec3::Vec3,
+};
+
+/// Parallelogram with a corner at `q` and sides `u` and `v`. Its front face is the one `u × v`
+/// points out of.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    /// `u × v` divided by its squared length, to find the planar coordinates of a point.
+    w: Vec3,
+    normal: Vec3,
+    /// Plane offset, `normal · p` for every point `p` on it.
+    d: f32,
+    area: f32,
+    material: Materials,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, m: Materials) -> Self {
+        let n = u.cross(v);
+        let normal = n.unit_vector();
+
+        Self {
+            q,
+            u,
+            v,
+            w: n / n.dot(n),
+            normal,
+            d: normal.dot(q),
+            area: n.len(),
+            material: m,
+        }
+    }
+
+    pub fn material(&self) -> &Materials {
+        &self.material
+    }
+}
+
+impl Hittable for Quad {
+    /// `u` and `v` of the hit are its coordinates along the sides, from 0 to 1.
+    fn hit_surface(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let denom = self.normal.dot(r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - self.normal.dot(r.origin)) / denom;
+        if !(t_min..=t_max).contains(&t) {
+            return None;
+        }
+
+        let point = r.at(t);
+        let planar = point - self.q;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        if !(0. ..=1.).contains(&alpha) || !(0. ..=1.).contains(&beta) {
+            return None;
+        }
+
+        let mut hr = HitRecord::new(point, self.normal, t, &self.material);
+        hr.set_face_normal(r);
+        (hr.u, hr.v, hr.dpdu, hr.dpdv) = (alpha, beta, self.u, self.v);
+
+        Some(hr)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let diagonal = Aabb::new(self.q, self.q + self.u + self.v);
+        let other = Aabb::new(self.q + self.u, self.q + self.v);
+
+        Some(diagonal.union(&other).pad(1e-4))
+    }
+
+    /// Points are sampled uniformly by area.
+    fn pdf_value(&self, origin: Point3, direction: Vec3, _time: f32) -> f32 {
+        let Some(hit) = self.hit_surface(&Ray::new(origin, direction), 0.001, f32::INFINITY) else {
+            return 0.;
+        };
+
+        let distance_squared = hit.t * hit.t * direction.len_squared();
+        let cosine = self.normal.dot(direction).abs() / direction.len();
+
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: Point3, _time: f32, u: (f32, f32)) -> Vec3 {
+        self.q + self.u * u.0 + self.v * u.1 - origin
+    }
+}
+
+/// Axis-aligned box, made of six quads facing out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cuboid {
+    sides: Box<[Quad; 6]>,
+    bounds: Aabb,
+    area: f32,
+}
+
+impl Cuboid {
+    /// Box between two opposite corners.
+    pub fn new(a: Point3, b: Point3, m: Materials) -> Self {
+        let bounds = Aabb::new(a, b);
+        let (min, max) = (bounds.min, bounds.max);
+        let size = max - min;
+        let dx = Vec3::new(size.x(), 0., 0.);
+        let dy = Vec3::new(0., size.y(), 0.);
+        let dz = Vec3::new(0., 0., size.z());
+
+        let side = |q, u, v| Quad::new(q, u, v, m.clone());
+        let sides = Box::new([
+            side(Point3::new(min.x(), min.y(), max.z()), dx, dy), // front
+            side(Point3::new(max.x(), min.y(), max.z()), -dz, dy), // right
+            side(Point3::new(max.x(), min.y(), min.z()), -dx, dy), // back
+            side(Point3::new(min.x(), min.y(), min.z()), dz, dy), // left
+            side(Point3::new(min.x(), max.y(), max.z()), dx, -dz), // top
+            side(Point3::new(min.x(), min.y(), min.z()), dx, dz), // bottom
+        ]);
+        let area = sides.iter().map(|s| s.area).sum();
+
+        Self {
+            sides,
+            bounds,
+            area,
+        }
+    }
+
+    pub fn material(&self) -> &Materials {
+        self.sides[0].material()
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit_surface(&self, r: &Ray, t_min: f32, mut t_max: f32) -> Option<HitRecord<'_>> {
+        let mut hit_record = None;
+
+        for side in self.sides.iter() {
+            if let Some(hit) = side.hit_surface(r, t_min, t_max) {
+                t_max = hit.t;
+                hit_record = Some(hit);
+            }
+        }
+
+        hit_record
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds.pad(1e-4))
+    }
+
+    /// A side is picked by its share of the area, and a point uniformly on it, so the densities
+    /// of the sides `direction` crosses add up weighted by their area.
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f32) -> f32 {
+        if self.area <= 0. {
+            return 0.;
+        }
+
+        self.sides
+            .iter()
+            .map(|s| s.pdf_value(origin, direction, time) * s.area)
+            .sum::<f32>()
+            / self.area
+    }
+
+    /// The first coordinate picks a side by area, then is reused to sample it.
+    fn random(&self, origin: Point3, time: f32, u: (f32, f32)) -> Vec3 {
+        let mut target = u.0 * self.area;
+
+        for side in self.sides.iter() {
+            if target < side.area {
+                return side.random(origin, time, (target / side.area, u.1));
+            }
+            target -= side.area;
+        }
+
+        self.sides[5].random(origin, time, (ONE_MINUS_EPSILON, u.1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cuboid, Quad};
+    use crate::{
+        hittable::Hittable,
+        material::{Lambertian, Materials},
+        ray::{Point3, Ray},
+        sampler::uniform_sphere,
+        vec3::Vec3,
+    };
+    use std::f32::consts::PI;
+
+    use fastrand::Rng;
+
+    fn material() -> Materials {
+        Materials::Lambertian(Lambertian::default())
+    }
+
+    #[test]
+    fn quad_coordinates_and_faces() {
+        let quad = Quad::new(
+            Point3::new(0., 0., 0.),
+            Vec3::new(2., 0., 0.),
+            Vec3::new(0., 1., 0.),
+            material(),
+        );
+
+        let front = quad
+            .hit_surface(
+                &Ray::new(Point3::new(1.5, 0.25, 1.), Vec3::new(0., 0., -1.)),
+                0.001,
+                f32::INFINITY,
+            )
+            .unwrap();
+        assert!(front.front_face);
+        assert_eq!(front.normal, Vec3::new(0., 0., 1.));
+        assert!((front.u - 0.75).abs() < 1e-6 && (front.v - 0.25).abs() < 1e-6);
+        assert_eq!(
+            (front.dpdu, front.dpdv),
+            (Vec3::new(2., 0., 0.), Vec3::new(0., 1., 0.))
+        );
+
+        let back = quad
+            .hit_surface(
+                &Ray::new(Point3::new(0.5, 0.5, -1.), Vec3::new(0., 0., 1.)),
+                0.001,
+                f32::INFINITY,
+            )
+            .unwrap();
+        assert!(!back.front_face);
+        assert_eq!(back.normal, Vec3::new(0., 0., -1.));
+
+        let outside = Ray::new(Point3::new(2.5, 0.5, 1.), Vec3::new(0., 0., -1.));
+        assert!(quad.hit_surface(&outside, 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn box_normals_face_out() {
+        let cuboid = Cuboid::new(
+            Point3::new(1., 1., 1.),
+            Point3::new(-1., -1., -1.),
+            material(),
+        );
+
+        for axis in 0..3 {
+            for sign in [-1., 1.] {
+                let mut outward = [0.; 3];
+                outward[axis] = sign;
+                let outward = Vec3::new(outward[0], outward[1], outward[2]);
+                let offset = Vec3::new(0.1, 0.2, 0.3);
+                let origin = outward * 3. + offset - outward * outward.dot(offset);
+
+                let hit = cuboid
+                    .hit_surface(&Ray::new(origin, -outward), 0.001, f32::INFINITY)
+                    .unwrap();
+                assert!(hit.front_face);
+                assert_eq!(hit.normal, outward);
+                assert!((hit.t - 2.).abs() < 1e-5);
+
+                // From inside, the back of the far side is hit.
+                let hit = cuboid
+                    .hit_surface(&Ray::new(Point3::default(), outward), 0.001, f32::INFINITY)
+                    .unwrap();
+                assert!(!hit.front_face);
+                assert_eq!(hit.normal, -outward);
+            }
+        }
+    }
+
+    #[test]
+    fn light_pdf_integrates_to_one() {
+        let rng = Rng::with_seed(1);
+        let cuboid = Cuboid::new(
+            Point3::new(-1., 0., -2.),
+            Point3::new(1., 0.5, -1.),
+            material(),
+        );
+        let origin = Point3::new(0.3, 2., 0.);
+
+        // Directions towards the box cross it twice, and either point could have been sampled.
+        let n = 200_000;
+        let integral = (0..n)
+            .map(|_| cuboid.pdf_value(origin, uniform_sphere((rng.f32(), rng.f32())), 0.))
+            .sum::<f32>()
+            * 4.
+            * PI
+            / n as f32;
+        assert!((integral - 1.).abs() < 0.03, "{integral}");
+
+        let direction = cuboid.random(origin, 0., (rng.f32(), rng.f32()));
+        assert!(cuboid.pdf_value(origin, direction, 0.) > 0.);
+    }
+}
@@ -0,0 +1,17 @@
+//! Seeded random numbers, so that renders can be reproduced.
+
+/// Hash of `values`, to seed random numbers that depend on several things, like the pixel and
+/// the sample. Every combination gets a stream of its own, so the image doesn't depend on which
+/// thread renders which pixel, or in which order.
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0, |h, &v| mix(h ^ v))
+}
+
+/// SplitMix64's finalizer, which spreads nearby inputs like consecutive pixels all over the
+/// range of seeds.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
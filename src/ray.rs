@@ -1,10 +1,4 @@
-use crate::{
-    hittable::{Hittable, HittableList},
-    material::{Material, Materials},
-    vec3::{Color, Vec3},
-};
-
-use std::{f32::INFINITY, ops::Mul};
+use crate::vec3::Vec3;
 
 pub type Point3 = Vec3;
 
@@ -12,6 +6,9 @@ pub type Point3 = Vec3;
 pub struct Ray {
     pub origin: Point3,
     pub direction: Vec3,
+    /// When the ray was shot, within the camera shutter interval. Moving objects are hit where
+    /// they are at that time.
+    pub time: f32,
 }
 
 /// How to Ray Trace
@@ -20,7 +17,15 @@ pub struct Ray {
 /// (3) compute a color for that intersection point.
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Self {
-        Self { origin, direction }
+        Self::with_time(origin, direction, 0.)
+    }
+
+    pub fn with_time(origin: Point3, direction: Vec3, time: f32) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+        }
     }
 
     /// P(t)=A+tb. Here P is a 3D position along a line in 3D. A is the ray origin and b is the ray
@@ -32,28 +37,3 @@ impl Ray {
         self.direction.mul_add_vec(t, self.origin)
     }
 }
-
-impl Ray {
-    pub fn color(&self, world: &HittableList, depth: u8) -> Color {
-        // If we've exceeded the ray bounce limit, no more light is gathered.
-        if depth == 0 {
-            return Color::new(0., 0., 0.);
-        }
-
-        if let Some(hit) = world.hit(self, 0.001, INFINITY) {
-            if let Some((scattered, attenuation)) = match hit.material {
-                Materials::Lambertian(l) => l.scatter(self, hit),
-                Materials::Metal(m) => m.scatter(self, hit),
-                Materials::Dielectric(d) => d.scatter(self, hit),
-            } {
-                attenuation * scattered.color(world, depth - 1)
-            } else {
-                Color::default()
-            }
-        } else {
-            let unit_direction = self.direction.unit_vector();
-            let t = (unit_direction.y() + 1.) * 0.5;
-            Color::new(1., 1., 1.).mul_add_vec(1. - t, Color::new(0.5, 0.7, 1.).mul(t))
-        }
-    }
-}
@@ -0,0 +1,547 @@
+//! Samplers hand out the random numbers of every sample, one dimension at a time, spread more
+//! evenly over the pixel's samples than independent random numbers would be.
+//!
+//! Every sample takes its dimensions in the same order, so that each one means the same thing
+//! in all the samples of a pixel: the position in the pixel and the point on the lens (2D each)
+//! and the time (1D), then at every bounce the light sample (2D), the BSDF sample (1D and 2D)
+//! and Russian roulette (1D). Values are in [0, 1).
+
+use crate::{random, vec3::Vec3};
+
+use std::f32::consts::PI;
+
+/// Largest `f32` below 1.
+pub const ONE_MINUS_EPSILON: f32 = 1. - f32::EPSILON / 2.;
+
+pub trait Sampler {
+    /// Starts over from the first dimension, for sample `index` of `pixel`.
+    fn start_pixel_sample(&mut self, pixel: u64, index: u32);
+
+    fn get_1d(&mut self) -> f32;
+
+    fn get_2d(&mut self) -> (f32, f32);
+}
+
+/// The kinds of sampler, as picked on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sampling {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl Sampling {
+    pub const NAMES: &'static str = "`independent`, `stratified`, `halton` or `sobol`";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "independent" => Some(Sampling::Independent),
+            "stratified" => Some(Sampling::Stratified),
+            "halton" => Some(Sampling::Halton),
+            "sobol" => Some(Sampling::Sobol),
+            _ => None,
+        }
+    }
+
+    /// Sampler for images that take `samples_per_pixel` samples in every pixel, drawing
+    /// different numbers for every `seed`.
+    pub fn sampler(self, seed: u64, samples_per_pixel: u32) -> Samplers {
+        let position = Position {
+            seed,
+            ..Position::default()
+        };
+
+        match self {
+            Sampling::Independent => Samplers::Independent(Independent { position }),
+            Sampling::Stratified => Samplers::Stratified(Stratified {
+                position,
+                samples_per_pixel,
+            }),
+            Sampling::Halton => Samplers::Halton(Halton { position }),
+            Sampling::Sobol => Samplers::Sobol(Sobol { position }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Samplers {
+    Independent(Independent),
+    Stratified(Stratified),
+    Halton(Halton),
+    Sobol(Sobol),
+}
+
+impl Sampler for Samplers {
+    fn start_pixel_sample(&mut self, pixel: u64, index: u32) {
+        match self {
+            Samplers::Independent(s) => s.start_pixel_sample(pixel, index),
+            Samplers::Stratified(s) => s.start_pixel_sample(pixel, index),
+            Samplers::Halton(s) => s.start_pixel_sample(pixel, index),
+            Samplers::Sobol(s) => s.start_pixel_sample(pixel, index),
+        }
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        match self {
+            Samplers::Independent(s) => s.get_1d(),
+            Samplers::Stratified(s) => s.get_1d(),
+            Samplers::Halton(s) => s.get_1d(),
+            Samplers::Sobol(s) => s.get_1d(),
+        }
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        match self {
+            Samplers::Independent(s) => s.get_2d(),
+            Samplers::Stratified(s) => s.get_2d(),
+            Samplers::Halton(s) => s.get_2d(),
+            Samplers::Sobol(s) => s.get_2d(),
+        }
+    }
+}
+
+/// Which sample of which pixel a sampler is at, and its next dimension.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Position {
+    seed: u64,
+    pixel: u64,
+    index: u32,
+    dimension: u64,
+}
+
+impl Position {
+    fn start(&mut self, pixel: u64, index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    /// Moves past the next `n` dimensions, returning the first of them.
+    fn take(&mut self, n: u64) -> u64 {
+        self.dimension += n;
+        self.dimension - n
+    }
+
+    /// Independent random value of dimension `d` for the current sample.
+    fn random(&self, d: u64) -> f32 {
+        let h = random::hash(&[self.seed, self.pixel, self.index as u64, d]);
+        unit((h >> 32) as u32)
+    }
+
+    /// Random bits shared by every sample of the pixel in dimension `d`, to scramble it with.
+    fn scramble(&self, d: u64) -> u64 {
+        random::hash(&[self.seed, self.pixel, d])
+    }
+}
+
+/// Maps 32 random bits to [0, 1).
+fn unit(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1 << 24) as f32
+}
+
+/// Every value independent of the others, like a plain random number generator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Independent {
+    position: Position,
+}
+
+impl Sampler for Independent {
+    fn start_pixel_sample(&mut self, pixel: u64, index: u32) {
+        self.position.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let d = self.position.take(1);
+        self.position.random(d)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let d = self.position.take(2);
+        (self.position.random(d), self.position.random(d + 1))
+    }
+}
+
+/// Jittered sampling: every dimension is split in as many strata as the pixel has samples, and
+/// each sample gets a random point in a stratum of its own. Strata are shuffled differently in
+/// every dimension, so that the dimensions aren't correlated. 2D dimensions are split in a grid
+/// of about square cells. Samples beyond `samples_per_pixel` are independent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stratified {
+    position: Position,
+    samples_per_pixel: u32,
+}
+
+impl Sampler for Stratified {
+    fn start_pixel_sample(&mut self, pixel: u64, index: u32) {
+        self.position.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let p = &mut self.position;
+        let d = p.take(1);
+        let n = self.samples_per_pixel;
+        if p.index >= n {
+            return p.random(d);
+        }
+
+        let stratum = permute(p.index, n, p.scramble(d) as u32);
+        ((stratum as f32 + p.random(d)) / n as f32).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let p = &mut self.position;
+        let d = p.take(2);
+        let columns = (self.samples_per_pixel as f32).sqrt().ceil() as u32;
+        let rows = self.samples_per_pixel.div_ceil(columns);
+        if p.index >= columns * rows {
+            return (p.random(d), p.random(d + 1));
+        }
+
+        let cell = permute(p.index, columns * rows, p.scramble(d) as u32);
+        (
+            (((cell % columns) as f32 + p.random(d)) / columns as f32).min(ONE_MINUS_EPSILON),
+            (((cell / columns) as f32 + p.random(d + 1)) / rows as f32).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
+
+/// Element `i` of a random permutation of `0..n` picked by `seed`, without storing it (Kensler,
+/// "Correlated Multi-Jittered Sampling", 2013).
+fn permute(mut i: u32, n: u32, seed: u32) -> u32 {
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    // Shuffles within the next power of two, until landing below `n`.
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+
+    (i + seed) % n
+}
+
+/// The Halton sequence, which takes dimension `d` from the digits of the sample's index in the
+/// `d`th prime base, shifted by a random offset in every pixel (Cranley-Patterson rotation).
+/// Dimensions past the table of primes are independent, since bases that large spread
+/// a pixel's few samples poorly anyway.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Halton {
+    position: Position,
+}
+
+const PRIMES: [u32; 64] = primes();
+
+const fn primes<const N: usize>() -> [u32; N] {
+    let mut primes = [0; N];
+    let mut count = 0;
+    let mut n = 2;
+    while count < N {
+        let mut i = 0;
+        while i < count && n % primes[i] != 0 {
+            i += 1;
+        }
+        if i == count {
+            primes[count] = n;
+            count += 1;
+        }
+        n += 1;
+    }
+
+    primes
+}
+
+impl Halton {
+    fn sample(&self, d: u64) -> f32 {
+        let p = &self.position;
+        let Some(&base) = PRIMES.get(d as usize) else {
+            return p.random(d);
+        };
+
+        let offset = (p.scramble(d) >> 11) as f64 / (1u64 << 53) as f64;
+        ((radical_inverse(base, p.index) + offset).fract() as f32).min(ONE_MINUS_EPSILON)
+    }
+}
+
+impl Sampler for Halton {
+    fn start_pixel_sample(&mut self, pixel: u64, index: u32) {
+        self.position.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let d = self.position.take(1);
+        self.sample(d)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let d = self.position.take(2);
+        (self.sample(d), self.sample(d + 1))
+    }
+}
+
+/// `i` written in `base`, with its digits mirrored around the radix point.
+fn radical_inverse(base: u32, mut i: u32) -> f64 {
+    let inverse_base = 1. / base as f64;
+    let mut scale = inverse_base;
+    let mut value = 0.;
+    while i > 0 {
+        value += (i % base) as f64 * scale;
+        scale *= inverse_base;
+        i /= base;
+    }
+
+    value
+}
+
+/// The first two dimensions of the Sobol sequence, Owen scrambled, and reused for every
+/// dimension with a differently shuffled order of the samples (Burley, "Practical Hash-based
+/// Owen Scrambling", 2020). The first 2ⁿ samples of a pixel are stratified along every 1D
+/// dimension, and in every way of splitting a 2D dimension into 2ⁿ equal rectangles, so
+/// powers of two are the best numbers of samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sobol {
+    position: Position,
+}
+
+/// Generator matrix of the Sobol sequence's second dimension, as its columns. The first one is
+/// the identity, which mirrors the bits of the index.
+const SOBOL_MATRIX: [u32; 32] = {
+    let mut columns = [1 << 31; 32];
+    let mut i = 1;
+    while i < 32 {
+        columns[i] = columns[i - 1] ^ (columns[i - 1] >> 1);
+        i += 1;
+    }
+
+    columns
+};
+
+impl Sobol {
+    /// Index of the current sample in dimension `d`'s shuffled order.
+    fn index(&self, d: u64) -> u32 {
+        owen_scramble(self.position.index, self.position.scramble(d) as u32)
+    }
+}
+
+impl Sampler for Sobol {
+    fn start_pixel_sample(&mut self, pixel: u64, index: u32) {
+        self.position.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let d = self.position.take(1);
+        let x = self.index(d).reverse_bits();
+
+        unit(owen_scramble(x, (self.position.scramble(d) >> 32) as u32))
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let d = self.position.take(2);
+        let index = self.index(d);
+
+        let x = index.reverse_bits();
+        let y = (0..32)
+            .filter(|bit| index & (1 << bit) != 0)
+            .fold(0, |y, bit| y ^ SOBOL_MATRIX[bit]);
+
+        (
+            unit(owen_scramble(x, (self.position.scramble(d) >> 32) as u32)),
+            unit(owen_scramble(
+                y,
+                (self.position.scramble(d + 1) >> 32) as u32,
+            )),
+        )
+    }
+}
+
+/// Randomly flips bits of `x` such that the flip of every bit only depends on the bits above
+/// it, which keeps the stratification of the Sobol sequence. The hash scrambles low bits
+/// depending on higher ones, so it works on the mirrored bits (Laine and Karras, 2011).
+fn owen_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+    x ^= x.wrapping_mul(0x3d20adea);
+    x = x.wrapping_add(seed);
+    x = x.wrapping_mul((seed >> 16) | 1);
+    x ^= x.wrapping_mul(0x05526c56);
+    x ^= x.wrapping_mul(0x53a22864);
+
+    x.reverse_bits()
+}
+
+/// Point on the unit disk, in the XY plane. Shirley and Chiu's concentric mapping keeps nearby
+/// samples nearby, so stratified samples stay stratified.
+pub fn uniform_disk(u: (f32, f32)) -> Vec3 {
+    let (a, b) = (2. * u.0 - 1., 2. * u.1 - 1.);
+    if a == 0. && b == 0. {
+        return Vec3::default();
+    }
+
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4. * (b / a))
+    } else {
+        (b, PI / 2. - PI / 4. * (a / b))
+    };
+
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.)
+}
+
+/// Direction above the XY plane with a density of its cosine with the z axis over π. Points of
+/// the unit disk lifted straight up onto the hemisphere are distributed that way (Malley's
+/// method).
+pub fn cosine_hemisphere(u: (f32, f32)) -> Vec3 {
+    let d = uniform_disk(u);
+    let z = (1. - d.x() * d.x() - d.y() * d.y()).max(0.).sqrt();
+
+    Vec3::new(d.x(), d.y(), z)
+}
+
+/// Direction with the same density all over the unit sphere.
+pub fn uniform_sphere(u: (f32, f32)) -> Vec3 {
+    let z = 1. - 2. * u.0;
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = 2. * PI * u.1;
+
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cosine_hemisphere, uniform_disk, Sampler, Sampling};
+    use crate::vec3::Vec3;
+
+    const ALL: [Sampling; 4] = [
+        Sampling::Independent,
+        Sampling::Stratified,
+        Sampling::Halton,
+        Sampling::Sobol,
+    ];
+
+    /// Values of `dimensions` consecutive 2D dimensions, for every sample of a pixel.
+    fn samples(sampling: Sampling, pixel: u64, n: u32, dimensions: usize) -> Vec<Vec<(f32, f32)>> {
+        let mut sampler = sampling.sampler(5, n);
+        (0..n)
+            .map(|i| {
+                sampler.start_pixel_sample(pixel, i);
+                (0..dimensions).map(|_| sampler.get_2d()).collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn uniform_and_reproducible() {
+        for sampling in ALL {
+            let values: Vec<f32> = (0..64)
+                .flat_map(|pixel| samples(sampling, pixel, 16, 8))
+                .flatten()
+                .flat_map(|(x, y)| [x, y])
+                .collect();
+
+            assert!(values.iter().all(|v| (0. ..1.).contains(v)));
+            let mean = values.iter().sum::<f32>() / values.len() as f32;
+            assert!((mean - 0.5).abs() < 0.01, "{sampling:?}: {mean}");
+
+            assert_eq!(samples(sampling, 3, 16, 8), samples(sampling, 3, 16, 8));
+            assert_ne!(samples(sampling, 3, 16, 8), samples(sampling, 4, 16, 8));
+        }
+    }
+
+    #[test]
+    fn stratified_dimensions() {
+        for sampling in [Sampling::Stratified, Sampling::Sobol] {
+            let pixel = samples(sampling, 9, 16, 6);
+
+            for d in 0..6 {
+                // One sample in every cell of a 4x4 grid.
+                let mut cells: Vec<_> = pixel
+                    .iter()
+                    .map(|s| ((s[d].0 * 4.) as u32, (s[d].1 * 4.) as u32))
+                    .collect();
+                cells.sort();
+                cells.dedup();
+                assert_eq!(cells.len(), 16, "{sampling:?}, dimension {d}");
+            }
+
+            let mut sampler = sampling.sampler(5, 16);
+            let mut strata: Vec<_> = (0..16)
+                .map(|i| {
+                    sampler.start_pixel_sample(9, i);
+                    sampler.get_2d();
+                    (sampler.get_1d() * 16.) as u32
+                })
+                .collect();
+            strata.sort();
+            assert_eq!(strata, (0..16).collect::<Vec<_>>(), "{sampling:?}");
+        }
+    }
+
+    #[test]
+    fn less_error_than_independent_samples() {
+        // Root mean square error over many pixels of the integral of a smooth function.
+        let error = |sampling: Sampling| {
+            let squared: f32 = (0..256)
+                .map(|pixel| {
+                    let estimate = samples(sampling, pixel, 16, 1)
+                        .iter()
+                        .map(|s| s[0].0 * s[0].1)
+                        .sum::<f32>()
+                        / 16.;
+                    (estimate - 0.25).powi(2)
+                })
+                .sum();
+            (squared / 256.).sqrt()
+        };
+
+        let independent = error(Sampling::Independent);
+        for sampling in [Sampling::Stratified, Sampling::Halton, Sampling::Sobol] {
+            let e = error(sampling);
+            assert!(e < 0.5 * independent, "{sampling:?}: {e} vs {independent}");
+        }
+    }
+
+    #[test]
+    fn concentric_disk() {
+        assert_eq!(uniform_disk((0.5, 0.5)).len(), 0.);
+        assert!((uniform_disk((1., 0.5)).x() - 1.).abs() < 1e-6);
+        assert!((uniform_disk((0.5, 0.)).y() + 1.).abs() < 1e-6);
+        assert!((0..100).all(|i| uniform_disk((i as f32 / 100., 0.93)).len() <= 1.));
+    }
+
+    #[test]
+    fn cosine_weighted_hemisphere() {
+        let mut sampler = Sampling::Sobol.sampler(3, 4096);
+        let mut mean_cosine = 0.;
+        for i in 0..4096 {
+            sampler.start_pixel_sample(0, i);
+            let w = cosine_hemisphere(sampler.get_2d());
+            assert!(w.z() >= 0. && (w.len() - 1.).abs() < 1e-5, "{w:?}");
+            mean_cosine += w.z() / 4096.;
+        }
+
+        // The mean of cos θ, over a density of cos θ / π, is 2/3.
+        assert!((mean_cosine - 2. / 3.).abs() < 1e-3, "{mean_cosine}");
+        assert_eq!(cosine_hemisphere((0.5, 0.5)), Vec3::new(0., 0., 1.));
+    }
+}
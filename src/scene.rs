@@ -0,0 +1,1182 @@
+//! Scene description files.
+//!
+//! Scenes are written in a small subset of TOML (see [`crate::toml`]). A `[camera]` table holds
+//! the arguments of [`Camera::new`], every `[[material]]` table defines a named material, and
+//! every `[[sphere]]`, `[[plane]]`, `[[quad]]`, `[[disk]]` or `[[box]]` table adds a shape that
+//! references one of those materials by name. `[[mesh]]` tables load Wavefront OBJ files and
+//! `[[volume]]` tables voxel grids, with paths relative to the scene file. Albedos are either
+//! a color or the name of a `[[texture]]`; textures can refer to the ones above them:
+//!
+//! ```toml
+//! [camera]
+//! lookfrom = [13, 2, 3]     # required
+//! lookat = [0, 0, 0]        # required
+//! vup = [0, 1, 0]           # default [0, 1, 0]
+//! vfov = 20                 # vertical field of view in degrees, default 20
+//! aperture = 0.1            # default 0 (pinhole)
+//! focus_dist = 10           # default |lookfrom - lookat|
+//! shutter_open = 0          # rays are shot at times between these, default 0 and 1
+//! shutter_close = 1
+//!
+//! [[texture]]
+//! name = "tiles"
+//! type = "checker"          # even, odd = [r, g, b] or texture, scale = cell size (default 1)
+//!
+//! [[texture]]
+//! name = "earth"
+//! type = "image"            # file = PPM or PNG, wrap = "repeat" (default), "clamp" or "mirror"
+//!
+//! [[texture]]
+//! name = "stone"
+//! type = "noise"            # style = "perlin" (default), "turbulence" or "marble",
+//!                           # scale = frequency (default 1), color = [r, g, b] (default white)
+//!
+//! [[texture]]
+//! name = "red"
+//! type = "solid"            # color = [r, g, b]
+//!
+//! [[material]]
+//! name = "ground"
+//! type = "lambertian"       # albedo = [r, g, b] or texture, sigma = 0 (smooth, default)
+//!                           # to 90, the spread in degrees of the grooves of rough surfaces
+//!
+//! [[material]]
+//! name = "steel"
+//! type = "metal"            # albedo = [r, g, b] or texture, the color at normal incidence,
+//!                           # or preset = "gold", "copper", "aluminium" or "silver",
+//!                           # or eta = [r, g, b] and k = [r, g, b], a complex index of refraction
+//! roughness = 0.3           # 0 (mirror, default) to 1, or [u, v] along the surface's
+//!                           # u and v directions for anisotropic highlights
+//!
+//! [[material]]
+//! name = "glass"
+//! type = "dielectric"       # ior = index of refraction, roughness as for metals,
+//!                           # tint = [r, g, b] filters light going through (default white),
+//!                           # absorption = [r, g, b] per unit of distance inside (default 0)
+//!
+//! [[material]]
+//! name = "paint"
+//! type = "principled"       # base_color = [r, g, b] or texture, and optionally, from 0 to 1:
+//!                           # metallic (default 0), roughness as for metals (default 0.5),
+//!                           # specular (default 0.5, 4% reflectance), specular_tint (default 0),
+//!                           # sheen (default 0), sheen_tint (default 0.5), clearcoat (default 0),
+//!                           # clearcoat_gloss (default 1), transmission (default 0), with
+//!                           # ior = index of refraction (default 1.5), and emission = [r, g, b]
+//!
+//! [[material]]
+//! name = "lamp"
+//! type = "diffuse_light"    # emit = [r, g, b] radiance, from the front face only
+//!
+//! [[material]]
+//! name = "smoke"
+//! type = "isotropic"        # for media, albedo = [r, g, b] or texture
+//! # type = "henyey_greenstein", also with g = -1..1, the mean cosine of the scattering angle
+//!
+//! [background]              # default: the white to blue sky gradient
+//! type = "gradient"         # bottom = [r, g, b], top = [r, g, b]
+//! # type = "solid"          # color = [r, g, b]
+//! # type = "black"
+//!
+//! [[sphere]]
+//! center = [0, 1, 0]        # at time 0
+//! center1 = [0, 1.5, 0]     # at time 1, for motion blur, default center
+//! radius = 1
+//! material = "glass"
+//!
+//! [[plane]]                 # infinite, never sampled as a light
+//! point = [0, 0, 0]
+//! normal = [0, 1, 0]        # towards the front face
+//! material = "ground"
+//!
+//! [[quad]]                  # parallelogram with a corner at q and sides u and v,
+//! q = [-1, 3, -1]           # facing along u × v
+//! u = [2, 0, 0]
+//! v = [0, 0, 2]
+//! material = "lamp"
+//!
+//! [[disk]]
+//! center = [3, 0.5, 0]
+//! normal = [0, 0, 1]
+//! radius = 0.5
+//! material = "steel"
+//!
+//! [[box]]                   # axis-aligned, between two opposite corners
+//! min = [-3, 0, -1]
+//! max = [-2, 1, 0]
+//! material = "ground"
+//!
+//! [[mesh]]
+//! file = "meshes/bunny.obj"
+//! material = "steel"
+//! group = "body"            # only load this OBJ group, default all of them
+//! name = "bunny"            # any shape: only a template for instances, not rendered itself
+//!
+//! [[volume]]                # medium with densities from a voxel grid, see [`crate::voxels`],
+//! file = "volumes/plume.vox" # stretched over the box between min and max
+//! min = [-1, 0, -1]
+//! max = [1, 2, 1]
+//! density = 4               # densities are multiplied by this, default 1
+//! emission = [8, 3, 1]      # radiance where the grid's emission is 1, default [0, 0, 0]
+//! material = "smoke"        # an isotropic or henyey_greenstein material
+//!
+//! [[instance]]              # places a named shape, sharing its geometry
+//! object = "bunny"
+//! material = "glass"        # default the shape's own
+//! scale = 2                 # any shape or instance: a factor or [x, y, z],
+//! rotate = [0, 45, 0]       # then degrees around the x, y and z axes,
+//! translate = [1, 0, -2]    # then an offset
+//! rotate1 = [0, 90, 0]      # scale1, rotate1 and translate1 are the same at time 1, when
+//!                           # shapes and instances move, and default to the above
+//!
+//! [[medium]]                # fog inside the closed shapes of a template
+//! boundary = "bunny"
+//! density = 0.5             # chance of scattering per unit of distance
+//! material = "smoke"        # an isotropic or henyey_greenstein material
+//! ```
+
+use crate::{
+    aabb::Aabb,
+    background::Background,
+    camera::Camera,
+    disk::Disk,
+    hittable::{HittableList, Primitives},
+    image::Image,
+    material::{Dielectric, DiffuseLight, Lambertian, Materials, Metal, Phase, Principled},
+    medium::{ConstantMedium, GridMedium},
+    obj,
+    perlin::Perlin,
+    plane::Plane,
+    quad::{Cuboid, Quad},
+    ray::Point3,
+    sphere::Sphere,
+    texture::{Checker, ImageTexture, Noise, NoiseStyle, Textures, Wrap},
+    toml::{self, Entry, Table, Value},
+    transform::{Instance, Pose},
+    triangle::TriangleMesh,
+    vec3::{self, Color, Vec3},
+    voxels::VoxelGrid,
+};
+
+use std::{collections::HashMap, fmt, fs, io, path::Path, sync::Arc};
+
+use fastrand::Rng;
+
+pub struct Scene {
+    pub camera: Camera,
+    pub world: HittableList,
+    /// The emissive objects of `world`, sampled directly by next-event estimation.
+    pub lights: HittableList,
+    pub background: Background,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Parse {
+        line: usize,
+        field: Option<String>,
+        message: String,
+    },
+    MissingTable(&'static str),
+}
+
+impl SceneError {
+    fn field(entry: &Entry, message: impl Into<String>) -> Self {
+        SceneError::Parse {
+            line: entry.line,
+            field: Some(entry.key.clone()),
+            message: message.into(),
+        }
+    }
+
+    fn table(table: &Table, message: impl Into<String>) -> Self {
+        SceneError::Parse {
+            line: table.line,
+            field: None,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "{e}"),
+            SceneError::Parse {
+                line,
+                field: Some(field),
+                message,
+            } => write!(f, "line {line}: field `{field}`: {message}"),
+            SceneError::Parse {
+                line,
+                field: None,
+                message,
+            } => write!(f, "line {line}: {message}"),
+            SceneError::MissingTable(name) => write!(f, "missing `[{name}]` table"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(e: io::Error) -> Self {
+        SceneError::Io(e)
+    }
+}
+
+impl From<toml::ParseError> for SceneError {
+    fn from(e: toml::ParseError) -> Self {
+        SceneError::Parse {
+            line: e.line,
+            field: None,
+            message: e.message,
+        }
+    }
+}
+
+impl Scene {
+    pub fn load(path: impl AsRef<Path>, aspect_ratio: f32) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let dir = path.parent().unwrap_or(Path::new("."));
+
+        Self::parse(&fs::read_to_string(path)?, dir, aspect_ratio)
+    }
+
+    /// Files referenced by the scene are looked up relative to `dir`.
+    pub fn parse(src: &str, dir: &Path, aspect_ratio: f32) -> Result<Self, SceneError> {
+        let doc = toml::parse(src)?;
+
+        let mut camera = None;
+        let mut background = Background::default();
+        let mut textures = HashMap::new();
+        let mut materials = HashMap::new();
+
+        for table in doc.tables.iter().filter(|t| t.name == "texture") {
+            let (name, texture) = parse_texture(table, dir, &textures)?;
+            if textures.insert(name.to_owned(), texture).is_some() {
+                return Err(SceneError::table(
+                    table,
+                    format!("texture `{name}` defined twice"),
+                ));
+            }
+        }
+
+        for table in &doc.tables {
+            match table.name.as_str() {
+                "camera" => camera = Some(parse_camera(table, aspect_ratio)?),
+                "background" => background = parse_background(table)?,
+                "material" => {
+                    let (name, material) = parse_material(table, &textures)?;
+                    if materials.insert(name.to_owned(), material).is_some() {
+                        return Err(SceneError::table(
+                            table,
+                            format!("material `{name}` defined twice"),
+                        ));
+                    }
+                }
+                "texture" | "sphere" | "plane" | "quad" | "disk" | "box" | "mesh" | "volume"
+                | "instance" | "medium" => {}
+                "" => Fields::new(table).finish()?,
+                name => return Err(SceneError::table(table, format!("unknown table `{name}`"))),
+            }
+        }
+
+        // Named shapes are only kept to be placed by `[[instance]]`s.
+        let mut world = HittableList::default();
+        let mut templates = HashMap::new();
+        for table in &doc.tables {
+            let mut fields = Fields::new(table);
+            let Some(shapes) = parse_shape(&mut fields, dir, &materials)? else {
+                continue;
+            };
+            let poses = fields.poses()?;
+            let name = fields.get("name").map(to_str).transpose()?;
+            fields.finish()?;
+
+            let shapes: Vec<Primitives> = match poses {
+                Some((start, end)) => shapes
+                    .into_iter()
+                    .map(|s| Instance::animated(Arc::new(s), start, end, None).into())
+                    .collect(),
+                None => shapes,
+            };
+
+            match name {
+                Some(name) => {
+                    let shapes: Vec<_> = shapes.into_iter().map(Arc::new).collect();
+                    if templates.insert(name, shapes).is_some() {
+                        return Err(SceneError::table(
+                            table,
+                            format!("shape `{name}` defined twice"),
+                        ));
+                    }
+                }
+                None => shapes.into_iter().for_each(|s| world.add(s)),
+            }
+        }
+
+        for table in doc.tables.iter().filter(|t| t.name == "instance") {
+            let mut fields = Fields::new(table);
+            let (object_entry, object) = fields.entry_str("object")?;
+            let material = match fields.get("material") {
+                Some(_) => Some(fields.material("material", &materials)?),
+                None => None,
+            };
+            let (start, end) = fields.poses()?.unwrap_or_default();
+            fields.finish()?;
+
+            let shapes = templates.get(object).ok_or_else(|| {
+                SceneError::field(object_entry, format!("unknown shape `{object}`"))
+            })?;
+            for shape in shapes {
+                world.add(Instance::animated(
+                    shape.clone(),
+                    start,
+                    end,
+                    material.clone(),
+                ));
+            }
+        }
+
+        for table in doc.tables.iter().filter(|t| t.name == "medium") {
+            let mut fields = Fields::new(table);
+            let (boundary_entry, boundary) = fields.entry_str("boundary")?;
+            let density_entry = fields.required("density")?;
+            let density = to_f32(density_entry)?;
+            let material = fields.phase("material", &materials)?;
+            fields.finish()?;
+
+            if density <= 0. {
+                return Err(SceneError::field(density_entry, "must be positive"));
+            }
+
+            let shapes = templates.get(boundary).ok_or_else(|| {
+                SceneError::field(boundary_entry, format!("unknown shape `{boundary}`"))
+            })?;
+            for shape in shapes {
+                world.add(ConstantMedium::new(
+                    shape.clone(),
+                    density,
+                    material.clone(),
+                ));
+            }
+        }
+
+        Ok(Self {
+            camera: camera.ok_or(SceneError::MissingTable("camera"))?,
+            lights: world.lights(),
+            world,
+            background,
+        })
+    }
+}
+
+/// The shapes a `[[sphere]]`, `[[plane]]`, `[[quad]]`, `[[disk]]`, `[[box]]`, `[[mesh]]` or
+/// `[[volume]]` table describes, `None` for other tables.
+fn parse_shape<'a>(
+    fields: &mut Fields<'a>,
+    dir: &Path,
+    materials: &HashMap<String, Materials>,
+) -> Result<Option<Vec<Primitives>>, SceneError> {
+    let shape: Primitives = match fields.table.name.as_str() {
+        "sphere" => {
+            let center = fields.vec3("center")?;
+            Sphere::moving(
+                center,
+                fields.vec3_or("center1", center)?,
+                fields.f32("radius")?,
+                fields.material("material", materials)?,
+            )
+            .into()
+        }
+        "plane" => Plane::new(
+            fields.vec3("point")?,
+            fields.vec3("normal")?,
+            fields.material("material", materials)?,
+        )
+        .into(),
+        "quad" => Quad::new(
+            fields.vec3("q")?,
+            fields.vec3("u")?,
+            fields.vec3("v")?,
+            fields.material("material", materials)?,
+        )
+        .into(),
+        "disk" => Disk::new(
+            fields.vec3("center")?,
+            fields.vec3("normal")?,
+            fields.f32("radius")?,
+            fields.material("material", materials)?,
+        )
+        .into(),
+        "box" => Cuboid::new(
+            fields.vec3("min")?,
+            fields.vec3("max")?,
+            fields.material("material", materials)?,
+        )
+        .into(),
+        "mesh" => return parse_mesh(fields, dir, materials).map(Some),
+        "volume" => parse_volume(fields, dir, materials)?.into(),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(vec![shape]))
+}
+
+/// One mesh per OBJ group, or just the requested one.
+fn parse_mesh<'a>(
+    fields: &mut Fields<'a>,
+    dir: &Path,
+    materials: &HashMap<String, Materials>,
+) -> Result<Vec<Primitives>, SceneError> {
+    let (file_entry, file) = fields.entry_str("file")?;
+    let material = fields.material("material", materials)?;
+    let group = fields.get("group").map(to_str).transpose()?;
+
+    let groups = obj::load(dir.join(file), material)
+        .map_err(|e| SceneError::field(file_entry, format!("{file}: {e}")))?;
+
+    let meshes: Vec<Primitives> = groups
+        .into_iter()
+        .filter(|g| group.is_none() || group == Some(g.name.as_str()))
+        .map(|g| TriangleMesh::new(g.mesh).into())
+        .collect();
+
+    if meshes.is_empty() {
+        let message = match group {
+            Some(name) => format!("{file} has no group `{name}`"),
+            None => format!("{file} has no faces"),
+        };
+        return Err(SceneError::field(file_entry, message));
+    }
+
+    Ok(meshes)
+}
+
+fn parse_volume<'a>(
+    fields: &mut Fields<'a>,
+    dir: &Path,
+    materials: &HashMap<String, Materials>,
+) -> Result<GridMedium, SceneError> {
+    let (file_entry, file) = fields.entry_str("file")?;
+    let bounds = Aabb::new(fields.vec3("min")?, fields.vec3("max")?);
+    let density_entry = fields.get("density");
+    let density = density_entry.map(to_f32).transpose()?.unwrap_or(1.);
+    let emission = fields.vec3_or("emission", Color::default())?;
+    let material = fields.phase("material", materials)?;
+
+    if bounds.extent().min_component() <= 0. {
+        return Err(SceneError::table(fields.table, "volume box is flat"));
+    }
+    if let Some(entry) = density_entry.filter(|_| density < 0.) {
+        return Err(SceneError::field(entry, "must not be negative"));
+    }
+
+    let grid = VoxelGrid::load(dir.join(file))
+        .map_err(|e| SceneError::field(file_entry, format!("{file}: {e}")))?;
+
+    Ok(GridMedium::new(
+        Arc::new(grid),
+        bounds,
+        density,
+        emission,
+        material,
+    ))
+}
+
+fn parse_camera(table: &Table, aspect_ratio: f32) -> Result<Camera, SceneError> {
+    let mut fields = Fields::new(table);
+
+    let lookfrom = fields.vec3("lookfrom")?;
+    let lookat = fields.vec3("lookat")?;
+    let vup = fields.vec3_or("vup", Vec3::new(0., 1., 0.))?;
+    let vfov = fields.f32_or("vfov", 20.)?;
+    let aperture = fields.f32_or("aperture", 0.)?;
+    let focus_dist = fields.f32_or("focus_dist", (lookfrom - lookat).len())?;
+    let shutter_open = fields.f32_or("shutter_open", 0.)?;
+    let shutter_close = fields.f32_or("shutter_close", 1.)?;
+    fields.finish()?;
+
+    Ok(Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        vfov,
+        aspect_ratio,
+        aperture,
+        focus_dist,
+    )
+    .with_shutter(shutter_open, shutter_close))
+}
+
+fn parse_background(table: &Table) -> Result<Background, SceneError> {
+    let mut fields = Fields::new(table);
+    let (kind_entry, kind) = fields.entry_str("type")?;
+
+    let background = match kind {
+        "gradient" => Background::Gradient {
+            bottom: fields.vec3("bottom")?,
+            top: fields.vec3("top")?,
+        },
+        "solid" => Background::Solid(fields.vec3("color")?),
+        "black" => Background::BLACK,
+        _ => {
+            return Err(SceneError::field(
+                kind_entry,
+                format!(
+                    "unknown background type `{kind}`, expected `gradient`, `solid` or `black`"
+                ),
+            ))
+        }
+    };
+    fields.finish()?;
+
+    Ok(background)
+}
+
+fn parse_texture<'a>(
+    table: &'a Table,
+    dir: &Path,
+    textures: &HashMap<String, Textures>,
+) -> Result<(&'a str, Textures), SceneError> {
+    let mut fields = Fields::new(table);
+
+    let name = fields.str("name")?;
+    let (kind_entry, kind) = fields.entry_str("type")?;
+
+    let texture = match kind {
+        "solid" => Textures::Solid(fields.vec3("color")?),
+        "checker" => Textures::Checker(Checker::new(
+            fields.f32_or("scale", 1.)?,
+            fields.texture("even", textures)?,
+            fields.texture("odd", textures)?,
+        )),
+        "image" => {
+            let (file_entry, file) = fields.entry_str("file")?;
+            let wrap = match fields.get("wrap") {
+                Some(entry) => {
+                    let name = to_str(entry)?;
+                    Wrap::from_name(name).ok_or_else(|| {
+                        SceneError::field(
+                            entry,
+                            format!("unknown wrap mode `{name}`, expected {}", Wrap::NAMES),
+                        )
+                    })?
+                }
+                None => Wrap::Repeat,
+            };
+
+            let image = Image::load(dir.join(file))
+                .map_err(|e| SceneError::field(file_entry, format!("{file}: {e}")))?;
+            Textures::Image(ImageTexture::new(Arc::new(image), wrap))
+        }
+        "noise" => {
+            let style = match fields.get("style") {
+                Some(entry) => {
+                    let name = to_str(entry)?;
+                    NoiseStyle::from_name(name).ok_or_else(|| {
+                        SceneError::field(
+                            entry,
+                            format!(
+                                "unknown noise style `{name}`, expected {}",
+                                NoiseStyle::NAMES
+                            ),
+                        )
+                    })?
+                }
+                None => NoiseStyle::Perlin,
+            };
+
+            Textures::Noise(Noise::new(
+                Arc::new(Perlin::new(0)),
+                style,
+                fields.f32_or("scale", 1.)?,
+                fields.vec3_or("color", Color::new(1., 1., 1.))?,
+            ))
+        }
+        _ => {
+            return Err(SceneError::field(
+                kind_entry,
+                format!(
+                    "unknown texture type `{kind}`, expected `solid`, `checker`, `image` or \
+                     `noise`"
+                ),
+            ))
+        }
+    };
+    fields.finish()?;
+
+    Ok((name, texture))
+}
+
+fn parse_material<'a>(
+    table: &'a Table,
+    textures: &HashMap<String, Textures>,
+) -> Result<(&'a str, Materials), SceneError> {
+    let mut fields = Fields::new(table);
+
+    let name = fields.str("name")?;
+    let (kind_entry, kind) = fields.entry_str("type")?;
+
+    let material = match kind {
+        "lambertian" => Materials::Lambertian(
+            Lambertian::new(fields.texture("albedo", textures)?)
+                .with_sigma(fields.f32_within("sigma", 0., 0., 90.)?),
+        ),
+        "metal" => {
+            let metal = match (fields.get("preset"), fields.get("eta")) {
+                (Some(_), Some(eta)) => {
+                    return Err(SceneError::field(eta, "can't be given with a `preset`"))
+                }
+                (Some(entry), None) => {
+                    let name = to_str(entry)?;
+                    Metal::preset(name).ok_or_else(|| {
+                        SceneError::field(
+                            entry,
+                            format!("unknown metal `{name}`, expected {}", Metal::PRESETS),
+                        )
+                    })?
+                }
+                (None, Some(eta)) => Metal::conductor(to_vec3(eta)?, fields.vec3("k")?),
+                (None, None) => Metal::new(fields.texture("albedo", textures)?),
+            };
+            let (along_u, along_v) = fields.roughness(0.)?;
+
+            Materials::Metal(metal.with_roughness(along_u, along_v))
+        }
+        "dielectric" => {
+            let (along_u, along_v) = fields.roughness(0.)?;
+
+            Materials::Dielectric(
+                Dielectric::new(fields.f32("ior")?)
+                    .with_roughness(along_u, along_v)
+                    .with_tint(fields.vec3_or("tint", Color::new(1., 1., 1.))?)
+                    .with_absorption(fields.vec3_or("absorption", Color::default())?),
+            )
+        }
+        "principled" => {
+            let defaults = Principled::default();
+
+            Materials::Principled(Principled {
+                base_color: fields.texture("base_color", textures)?,
+                metallic: fields.fraction("metallic", defaults.metallic)?,
+                roughness: fields.roughness(0.5)?,
+                specular: fields.fraction("specular", defaults.specular)?,
+                specular_tint: fields.fraction("specular_tint", defaults.specular_tint)?,
+                sheen: fields.fraction("sheen", defaults.sheen)?,
+                sheen_tint: fields.fraction("sheen_tint", defaults.sheen_tint)?,
+                clearcoat: fields.fraction("clearcoat", defaults.clearcoat)?,
+                clearcoat_gloss: fields.fraction("clearcoat_gloss", defaults.clearcoat_gloss)?,
+                transmission: fields.fraction("transmission", defaults.transmission)?,
+                ior: fields.f32_or("ior", defaults.ior)?,
+                emission: fields.vec3_or("emission", defaults.emission)?,
+            })
+        }
+        "diffuse_light" => Materials::DiffuseLight(DiffuseLight::new(fields.vec3("emit")?)),
+        "isotropic" => Materials::Phase(Phase::isotropic(fields.texture("albedo", textures)?)),
+        "henyey_greenstein" => Materials::Phase(Phase::henyey_greenstein(
+            fields.texture("albedo", textures)?,
+            fields.f32("g")?,
+        )),
+        _ => {
+            return Err(SceneError::field(
+                kind_entry,
+                format!(
+                    "unknown material type `{kind}`, expected `lambertian`, `metal`, \
+                     `dielectric`, `principled`, `diffuse_light`, `isotropic` or \
+                     `henyey_greenstein`"
+                ),
+            ))
+        }
+    };
+    fields.finish()?;
+
+    Ok((name, material))
+}
+
+/// Typed access to the fields of a table that remembers which ones were read, so that unknown
+/// (usually misspelled) fields can be reported instead of silently ignored.
+struct Fields<'a> {
+    table: &'a Table,
+    used: Vec<&'a str>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(table: &'a Table) -> Self {
+        Self {
+            table,
+            used: Vec::new(),
+        }
+    }
+
+    fn get(&mut self, key: &'a str) -> Option<&'a Entry> {
+        self.used.push(key);
+        self.table.entries.iter().find(|e| e.key == key)
+    }
+
+    fn required(&mut self, key: &'a str) -> Result<&'a Entry, SceneError> {
+        self.get(key).ok_or_else(|| SceneError::Parse {
+            line: self.table.line,
+            field: Some(key.to_owned()),
+            message: format!("missing in `[{}]`", self.table.name),
+        })
+    }
+
+    fn f32(&mut self, key: &'a str) -> Result<f32, SceneError> {
+        to_f32(self.required(key)?)
+    }
+
+    fn f32_or(&mut self, key: &'a str, default: f32) -> Result<f32, SceneError> {
+        self.get(key).map_or(Ok(default), to_f32)
+    }
+
+    /// A number between `min` and `max`.
+    fn f32_within(
+        &mut self,
+        key: &'a str,
+        default: f32,
+        min: f32,
+        max: f32,
+    ) -> Result<f32, SceneError> {
+        let Some(entry) = self.get(key) else {
+            return Ok(default);
+        };
+
+        let value = to_f32(entry)?;
+        if !(min..=max).contains(&value) {
+            return Err(SceneError::field(
+                entry,
+                format!("must be between {min} and {max}"),
+            ));
+        }
+
+        Ok(value)
+    }
+
+    /// A number between 0 and 1.
+    fn fraction(&mut self, key: &'a str, default: f32) -> Result<f32, SceneError> {
+        self.f32_within(key, default, 0., 1.)
+    }
+
+    fn vec3(&mut self, key: &'a str) -> Result<Vec3, SceneError> {
+        to_vec3(self.required(key)?)
+    }
+
+    fn vec3_or(&mut self, key: &'a str, default: Vec3) -> Result<Vec3, SceneError> {
+        self.get(key).map_or(Ok(default), to_vec3)
+    }
+
+    fn entry_str(&mut self, key: &'a str) -> Result<(&'a Entry, &'a str), SceneError> {
+        let entry = self.required(key)?;
+        Ok((entry, to_str(entry)?))
+    }
+
+    fn str(&mut self, key: &'a str) -> Result<&'a str, SceneError> {
+        self.entry_str(key).map(|(_, s)| s)
+    }
+
+    /// Either a color or the name of a texture.
+    fn texture(
+        &mut self,
+        key: &'a str,
+        textures: &HashMap<String, Textures>,
+    ) -> Result<Textures, SceneError> {
+        let entry = self.required(key)?;
+
+        match &entry.value {
+            Value::String(name) => textures
+                .get(name)
+                .cloned()
+                .ok_or_else(|| SceneError::field(entry, format!("unknown texture `{name}`"))),
+            Value::Array(_) => to_vec3(entry).map(Textures::Solid),
+            v => Err(SceneError::field(
+                entry,
+                format!(
+                    "expected a color or a texture name, found {}",
+                    v.type_name()
+                ),
+            )),
+        }
+    }
+
+    fn material(
+        &mut self,
+        key: &'a str,
+        materials: &HashMap<String, Materials>,
+    ) -> Result<Materials, SceneError> {
+        let (entry, name) = self.entry_str(key)?;
+        materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| SceneError::field(entry, format!("unknown material `{name}`")))
+    }
+
+    /// A material for media, which only scatter light through phase functions.
+    fn phase(
+        &mut self,
+        key: &'a str,
+        materials: &HashMap<String, Materials>,
+    ) -> Result<Materials, SceneError> {
+        let material = self.material(key, materials)?;
+        if !matches!(material, Materials::Phase(_)) {
+            return Err(SceneError::field(
+                self.get(key).unwrap(),
+                "expected an `isotropic` or `henyey_greenstein` material",
+            ));
+        }
+
+        Ok(material)
+    }
+
+    /// Poses at times 0 and 1 from the optional `scale`, `rotate` and `translate` fields, and
+    /// their `scale1`, `rotate1` and `translate1` counterparts that default to them. `None`
+    /// without any of them.
+    fn poses(&mut self) -> Result<Option<(Pose, Pose)>, SceneError> {
+        let scale = self.scale("scale")?;
+        let rotate = self.get("rotate").map(to_vec3).transpose()?;
+        let translate = self.get("translate").map(to_vec3).transpose()?;
+        let scale1 = self.scale("scale1")?;
+        let rotate1 = self.get("rotate1").map(to_vec3).transpose()?;
+        let translate1 = self.get("translate1").map(to_vec3).transpose()?;
+
+        if [scale, rotate, translate, scale1, rotate1, translate1]
+            .iter()
+            .all(Option::is_none)
+        {
+            return Ok(None);
+        }
+
+        let default = Pose::default();
+        let start = Pose {
+            scale: scale.unwrap_or(default.scale),
+            rotate: rotate.unwrap_or(default.rotate),
+            translate: translate.unwrap_or(default.translate),
+        };
+        let end = Pose {
+            scale: scale1.unwrap_or(start.scale),
+            rotate: rotate1.unwrap_or(start.rotate),
+            translate: translate1.unwrap_or(start.translate),
+        };
+
+        Ok(Some((start, end)))
+    }
+
+    /// A number or a factor per axis, none of them zero.
+    fn scale(&mut self, key: &'a str) -> Result<Option<Vec3>, SceneError> {
+        let Some(entry) = self.get(key) else {
+            return Ok(None);
+        };
+
+        let factors = match entry.value {
+            Value::Number(s) => Vec3::splat(s),
+            _ => to_vec3(entry)?,
+        };
+        if (0..3).any(|i| factors[i] == 0.) {
+            return Err(SceneError::field(entry, "can't scale by zero"));
+        }
+
+        Ok(Some(factors))
+    }
+
+    /// A perceptual roughness from 0 to 1, or two of them along the `u` and `v` directions of
+    /// the surface, 0 by default.
+    fn roughness(&mut self, default: f32) -> Result<(f32, f32), SceneError> {
+        let Some(entry) = self.get("roughness") else {
+            return Ok((default, default));
+        };
+
+        let (u, v) = match &entry.value {
+            Value::Number(r) => (*r, *r),
+            Value::Array(a) => match a.as_slice() {
+                [Value::Number(u), Value::Number(v)] => (*u, *v),
+                _ => return Err(SceneError::field(entry, "expected an array of 2 numbers")),
+            },
+            v => {
+                return Err(SceneError::field(
+                    entry,
+                    format!("expected a number or 2 of them, found {}", v.type_name()),
+                ))
+            }
+        };
+        if !((0. ..=1.).contains(&u) && (0. ..=1.).contains(&v)) {
+            return Err(SceneError::field(entry, "must be between 0 and 1"));
+        }
+
+        Ok((u, v))
+    }
+
+    fn finish(self) -> Result<(), SceneError> {
+        match self
+            .table
+            .entries
+            .iter()
+            .find(|e| !self.used.contains(&e.key.as_str()))
+        {
+            Some(entry) => Err(SceneError::field(entry, "unknown field")),
+            None => Ok(()),
+        }
+    }
+}
+
+fn to_f32(entry: &Entry) -> Result<f32, SceneError> {
+    match entry.value {
+        Value::Number(n) => Ok(n),
+        ref v => Err(SceneError::field(
+            entry,
+            format!("expected a number, found {}", v.type_name()),
+        )),
+    }
+}
+
+fn to_str(entry: &Entry) -> Result<&str, SceneError> {
+    match &entry.value {
+        Value::String(s) => Ok(s),
+        v => Err(SceneError::field(
+            entry,
+            format!("expected a string, found {}", v.type_name()),
+        )),
+    }
+}
+
+fn to_vec3(entry: &Entry) -> Result<Vec3, SceneError> {
+    match &entry.value {
+        Value::Array(a) => match a.as_slice() {
+            [Value::Number(x), Value::Number(y), Value::Number(z)] => Ok(Vec3::new(*x, *y, *z)),
+            _ => Err(SceneError::field(entry, "expected an array of 3 numbers")),
+        },
+        v => Err(SceneError::field(
+            entry,
+            format!("expected an array of 3 numbers, found {}", v.type_name()),
+        )),
+    }
+}
+
+/// The final scene of _Ray Tracing in One Weekend_, with small spheres placed by `rng`.
+pub fn random_scene(aspect_ratio: f32, rng: &Rng) -> Scene {
+    let lookfrom = Point3::new(13., 2., 3.);
+    let lookat = Point3::new(0., 0., 0.);
+    let vup = Point3::new(0., 1., 0.);
+    let dist_to_focus = 10.;
+    let aperture = 0.1;
+
+    let camera = Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        20.,
+        aspect_ratio,
+        aperture,
+        dist_to_focus,
+    );
+
+    let mut world = HittableList::default();
+
+    world.add(Plane::new(
+        Point3::new(0., 0., 0.),
+        Vec3::new(0., 1., 0.),
+        Materials::Lambertian(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    ));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let a = a as f32;
+            let b = b as f32;
+
+            let choose_mat = rng.f32();
+            let center = Point3::new(a + 0.9 * rng.f32(), 0.2, b + 0.9 * rng.f32());
+
+            if (center - Point3::new(4., 0.2, 0.)).len() <= 0.8 {
+                continue;
+            }
+
+            let material = if choose_mat < 0.8 {
+                // Diffuse
+                let albedo = Color::random(rng) * Color::random(rng);
+                Materials::Lambertian(Lambertian::new(albedo))
+            } else if choose_mat < 0.95 {
+                // Metal
+                let albedo = Color::random_in_range(0.5, 1., rng);
+                let roughness = vec3::random_f32(0., 0.5, rng);
+                Materials::Metal(Metal::new(albedo).with_roughness(roughness, roughness))
+            } else {
+                // Glass
+                Materials::Dielectric(Dielectric::new(1.5))
+            };
+
+            world.add(Sphere::new(center, 0.2, material));
+        }
+    }
+
+    world.add(Sphere::new(
+        Vec3::new(0., 1., 0.),
+        1.0,
+        Materials::Dielectric(Dielectric::new(1.5)),
+    ));
+
+    world.add(Sphere::new(
+        Vec3::new(-4., 1., 0.),
+        1.0,
+        Materials::Lambertian(Lambertian::new(Color::new(0.4, 0.2, 0.1))),
+    ));
+
+    world.add(Sphere::new(
+        Vec3::new(3.5, 1., 0.),
+        1.0,
+        Materials::Metal(Metal::new(Color::new(0.7, 0.6, 0.5))),
+    ));
+
+    Scene {
+        camera,
+        world,
+        lights: HittableList::default(),
+        background: Background::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scene, SceneError};
+    use crate::hittable::Hittable;
+    use std::path::Path;
+
+    fn parse_err(src: &str) -> (usize, Option<String>) {
+        match Scene::parse(src, Path::new("."), 1.) {
+            Err(SceneError::Parse { line, field, .. }) => (line, field),
+            Err(e) => panic!("unexpected error {e}"),
+            Ok(_) => panic!("scene parsed"),
+        }
+    }
+
+    #[test]
+    fn parses_scene() {
+        let scene = Scene::parse(
+            r#"
+            [camera]
+            lookfrom = [0, 0, 1]
+            lookat = [0, 0, 0]
+
+            [[sphere]]
+            center = [0, 0, -1]
+            radius = 0.5
+            material = "red"
+
+            [[material]]
+            name = "red"
+            type = "lambertian"
+            albedo = [0.8, 0.1, 0.1]
+            "#,
+            Path::new("."),
+            1.,
+        );
+
+        assert!(scene.is_ok());
+    }
+
+    #[test]
+    fn textures() {
+        let src = |albedo: &str| {
+            format!(
+                r#"
+                [camera]
+                lookfrom = [0, 0, 1]
+                lookat = [0, 0, 0]
+
+                [[texture]]
+                name = "white"
+                type = "solid"
+                color = [1, 1, 1]
+
+                [[texture]]
+                name = "checks"
+                type = "checker"
+                even = "white"
+                odd = [0, 0, 0]
+                scale = 0.5
+
+                [[texture]]
+                name = "marble"
+                type = "noise"
+                style = "marble"
+
+                [[material]]
+                name = "ground"
+                type = "lambertian"
+                albedo = {albedo}
+                "#
+            )
+        };
+
+        assert!(Scene::parse(&src("\"checks\""), Path::new("."), 1.).is_ok());
+        assert!(Scene::parse(&src("\"marble\""), Path::new("."), 1.).is_ok());
+        assert_eq!(parse_err(&src("\"wood\"")), (26, Some("albedo".into())));
+        assert_eq!(parse_err(&src("0.5")), (26, Some("albedo".into())));
+    }
+
+    #[test]
+    fn instances() {
+        let src = |object: &str, scale: &str| {
+            format!(
+                r#"
+                [camera]
+                lookfrom = [0, 0, 1]
+                lookat = [0, 0, 0]
+
+                [[material]]
+                name = "white"
+                type = "lambertian"
+                albedo = [1, 1, 1]
+
+                [[box]]
+                name = "crate"
+                min = [0, 0, 0]
+                max = [1, 1, 1]
+                material = "white"
+
+                [[instance]]
+                object = "{object}"
+                scale = {scale}
+                rotate = [0, 30, 0]
+                translate = [2, 0, 0]
+                "#
+            )
+        };
+
+        let scene = Scene::parse(&src("crate", "[1, 2, 1]"), Path::new("."), 1.).unwrap();
+        let bounds = scene.world.bounding_box().unwrap();
+        assert!((bounds.max.y() - 2.).abs() < 1e-5 && bounds.min.x() > 1.9);
+
+        assert_eq!(parse_err(&src("barrel", "1")), (18, Some("object".into())));
+        assert_eq!(parse_err(&src("crate", "0")), (19, Some("scale".into())));
+    }
+
+    #[test]
+    fn reports_line_and_field() {
+        let camera = "[camera]\nlookfrom = [0, 0, 1]\nlookat = [0, 0, 0]\n";
+
+        assert_eq!(
+            parse_err(&format!("{camera}[[sphere]]\ncenter = [0, 0]\n")),
+            (5, Some("center".into()))
+        );
+        assert_eq!(
+            parse_err(&format!(
+                "{camera}[[sphere]]\ncenter = [0, 0, 0]\nradius = \"big\"\n"
+            )),
+            (6, Some("radius".into()))
+        );
+        assert_eq!(
+            parse_err(&format!(
+                "{camera}[[sphere]]\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"nope\"\n"
+            )),
+            (7, Some("material".into()))
+        );
+        assert_eq!(
+            parse_err("[camera]\nlookfrom = [0, 0, 1]\nlookat = [0, 0, 0]\nfov = 3\n"),
+            (4, Some("fov".into()))
+        );
+        assert_eq!(
+            parse_err("[camera]\nlookat = [0, 0, 0]\n"),
+            (1, Some("lookfrom".into()))
+        );
+    }
+}
@@ -1,30 +1,83 @@
 use crate::{
+    aabb::Aabb,
     hittable::{HitRecord, Hittable},
     material::Materials,
+    onb::Onb,
     ray::{Point3, Ray},
+    sampler,
     vec3::Vec3,
 };
 
+use std::f32::consts::PI;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Sphere {
+    /// Center at time 0.
     center: Point3,
+    /// How far the center moves from time 0 to time 1.
+    motion: Vec3,
     radius: f32,
     material: Materials,
 }
 
 impl Sphere {
     pub fn new(center: Point3, radius: f32, m: Materials) -> Self {
+        Self::moving(center, center, radius, m)
+    }
+
+    /// Sphere moving in a straight line from `center0` at time 0 to `center1` at time 1, and
+    /// resting at either end outside that interval.
+    pub fn moving(center0: Point3, center1: Point3, radius: f32, m: Materials) -> Self {
         Self {
-            center,
+            center: center0,
+            motion: center1 - center0,
             radius,
             material: m,
         }
     }
+
+    fn center(&self, time: f32) -> Point3 {
+        self.center + self.motion * time.clamp(0., 1.)
+    }
+
+    pub fn material(&self) -> &Materials {
+        &self.material
+    }
+
+    /// Spherical coordinates of the point with outward unit normal `n`: `u` goes around the y
+    /// axis starting from -x, and `v` from the bottom pole (-y) to the top one. Returns `(u, v)`
+    /// and the derivatives of the point along them.
+    fn parameterisation(&self, n: Vec3) -> (f32, f32, Vec3, Vec3) {
+        let theta = (-n.y()).clamp(-1., 1.).acos();
+        let phi = (-n.z()).atan2(n.x()) + PI;
+        let r = self.radius.abs();
+
+        let dpdu = Vec3::new(n.z(), 0., -n.x()) * (2. * PI * r);
+        let rho = (n.x() * n.x() + n.z() * n.z()).sqrt();
+        let dpdv = if rho > 1e-6 {
+            Vec3::new(-n.x() * n.y() / rho, rho, -n.y() * n.z() / rho) * (PI * r)
+        } else {
+            // At the poles u is degenerate, any tangent will do.
+            Onb::from_w(n).u
+        };
+
+        (phi / (2. * PI), theta / PI, dpdu, dpdv)
+    }
+
+    /// Cosine of the half-angle of the cone the sphere centered at `center` covers seen from
+    /// `origin`, `None` from inside.
+    fn cos_theta_max(&self, center: Point3, origin: Point3) -> Option<f32> {
+        let distance_squared = (center - origin).len_squared();
+        let radius_squared = self.radius * self.radius;
+
+        (distance_squared > radius_squared).then(|| (1. - radius_squared / distance_squared).sqrt())
+    }
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let oc = r.origin - self.center;
+    fn hit_surface(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let center = self.center(r.time);
+        let oc = r.origin - center;
         let a = r.direction.len_squared();
         let half_b = oc.dot(r.direction);
         let c = oc.len_squared() - self.radius.powi(2);
@@ -40,46 +93,151 @@ impl Hittable for Sphere {
         let mut root = (-half_b - sqrtd) / a;
 
         if root < t_min || t_max < root {
-            root = (-half_b - sqrtd) / a;
+            root = (-half_b + sqrtd) / a;
             if root < t_min || t_max < root {
                 return None;
             }
         }
 
         let point = r.at(root);
+        let outward_normal = (point - center) / self.radius;
 
-        let mut hr = HitRecord::new(
-            point,
-            (point - self.center) / self.radius,
-            root,
-            self.material,
-        );
+        let mut hr = HitRecord::new(point, outward_normal, root, &self.material);
         hr.set_face_normal(r);
+        (hr.u, hr.v, hr.dpdu, hr.dpdv) = self.parameterisation(outward_normal);
 
         Some(hr)
     }
-}
 
-pub fn random_in_unit_sphere() -> Vec3 {
-    loop {
-        let p = Vec3::random_in_range(-1., 1.);
+    /// Covers the whole motion.
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::splat(self.radius.abs());
+        let (start, end) = (self.center(0.), self.center(1.));
+
+        Some(Aabb::new(start - r, start + r).union(&Aabb::new(end - r, end + r)))
+    }
+
+    /// Directions are sampled uniformly within the cone the sphere covers.
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f32) -> f32 {
+        let Some(cos_theta_max) = self.cos_theta_max(self.center(time), origin) else {
+            return 0.;
+        };
 
-        if p.len_squared() < 1. {
-            return p;
+        if self
+            .hit_surface(
+                &Ray::with_time(origin, direction, time),
+                0.001,
+                f32::INFINITY,
+            )
+            .is_none()
+        {
+            return 0.;
         }
+
+        1. / (2. * PI * (1. - cos_theta_max))
     }
-}
 
-pub fn _random_in_hemisphere(normal: Vec3) -> Vec3 {
-    let in_unit_sphere = random_in_unit_sphere();
-    if in_unit_sphere.dot(normal) > 0. {
-        // In the same hemisphere as the normal
-        in_unit_sphere
-    } else {
-        -in_unit_sphere
+    fn random(&self, origin: Point3, time: f32, u: (f32, f32)) -> Vec3 {
+        let center = self.center(time);
+        let Some(cos_theta_max) = self.cos_theta_max(center, origin) else {
+            return sampler::uniform_sphere(u);
+        };
+
+        let (r1, r2) = u;
+        let z = 1. + r2 * (cos_theta_max - 1.);
+        let phi = 2. * PI * r1;
+        let sin_theta = (1. - z * z).max(0.).sqrt();
+
+        Onb::from_w(center - origin).local(Vec3::new(
+            phi.cos() * sin_theta,
+            phi.sin() * sin_theta,
+            z,
+        ))
     }
 }
 
-pub fn random_unit_vector() -> Vec3 {
-    random_in_unit_sphere().unit_vector()
+#[cfg(test)]
+mod tests {
+    use super::Sphere;
+    use crate::{
+        hittable::Hittable,
+        material::{Lambertian, Materials},
+        ray::{Point3, Ray},
+        vec3::Vec3,
+    };
+    use std::f32::consts::PI;
+
+    #[test]
+    fn spherical_mapping() {
+        let center = Point3::new(1., 2., 3.);
+        let radius = 2.;
+        let sphere = Sphere::new(center, radius, Materials::Lambertian(Lambertian::default()));
+
+        let at = |u: f32, v: f32| {
+            let (theta, phi) = (v * PI, u * 2. * PI);
+            center
+                + Vec3::new(
+                    -theta.sin() * phi.cos(),
+                    -theta.cos(),
+                    theta.sin() * phi.sin(),
+                ) * radius
+        };
+
+        for direction in [
+            Vec3::new(1., 0.3, 0.2),
+            Vec3::new(-0.5, -0.8, 0.1),
+            Vec3::new(0.1, 0.2, -1.),
+        ] {
+            let origin = center + direction * 10.;
+            let hit = sphere
+                .hit_surface(&Ray::new(origin, -direction), 0.001, f32::INFINITY)
+                .unwrap();
+
+            assert!((at(hit.u, hit.v) - hit.point).len() < 1e-4);
+
+            let h = 1e-3;
+            let dpdu = (at(hit.u + h, hit.v) - at(hit.u - h, hit.v)) / (2. * h);
+            let dpdv = (at(hit.u, hit.v + h) - at(hit.u, hit.v - h)) / (2. * h);
+            assert!((dpdu - hit.dpdu).len() < 1e-2 * dpdu.len());
+            assert!((dpdv - hit.dpdv).len() < 1e-2 * dpdv.len());
+        }
+
+        // From inside, the far side is hit.
+        let hit = sphere.hit_surface(
+            &Ray::new(center, Vec3::new(1., 0., 0.)),
+            0.001,
+            f32::INFINITY,
+        );
+        assert!((hit.unwrap().t - radius).abs() < 1e-5);
+    }
+
+    #[test]
+    fn moving_center() {
+        let sphere = Sphere::moving(
+            Point3::new(0., 0., 0.),
+            Point3::new(2., 0., 0.),
+            0.5,
+            Materials::Lambertian(Lambertian::default()),
+        );
+        let down = |x: f32, time: f32| {
+            Ray::with_time(Point3::new(x, 5., 0.), Vec3::new(0., -1., 0.), time)
+        };
+
+        assert!(sphere
+            .hit_surface(&down(0., 0.), 0.001, f32::INFINITY)
+            .is_some());
+        assert!(sphere
+            .hit_surface(&down(0., 1.), 0.001, f32::INFINITY)
+            .is_none());
+        assert!(sphere
+            .hit_surface(&down(2., 1.), 0.001, f32::INFINITY)
+            .is_some());
+        assert!(sphere
+            .hit_surface(&down(1., 0.5), 0.001, f32::INFINITY)
+            .is_some());
+
+        let bounds = sphere.bounding_box().unwrap();
+        assert_eq!(bounds.min, Point3::new(-0.5, -0.5, -0.5));
+        assert_eq!(bounds.max, Point3::new(2.5, 0.5, 0.5));
+    }
 }
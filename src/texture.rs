@@ -0,0 +1,277 @@
+//! Textures give materials colors that vary over their surface.
+
+use crate::{image::Image, perlin::Perlin, ray::Point3, vec3::Color};
+
+use std::sync::Arc;
+
+pub trait Texture {
+    /// Color at surface coordinates `(u, v)` and point `p` of a hit.
+    fn value(&self, u: f32, v: f32, p: Point3) -> Color;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Textures {
+    Solid(Color),
+    Checker(Checker),
+    Image(ImageTexture),
+    Noise(Noise),
+}
+
+impl Texture for Textures {
+    fn value(&self, u: f32, v: f32, p: Point3) -> Color {
+        match self {
+            Textures::Solid(c) => *c,
+            Textures::Checker(c) => c.value(u, v, p),
+            Textures::Image(i) => i.value(u, v, p),
+            Textures::Noise(n) => n.value(u, v, p),
+        }
+    }
+}
+
+impl Default for Textures {
+    fn default() -> Self {
+        Textures::Solid(Color::default())
+    }
+}
+
+impl From<Color> for Textures {
+    fn from(c: Color) -> Self {
+        Textures::Solid(c)
+    }
+}
+
+/// Alternates between two textures in a 3D grid of cubes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checker {
+    inverse_scale: f32,
+    even: Box<Textures>,
+    odd: Box<Textures>,
+}
+
+impl Checker {
+    /// `scale` is the side of the cubes.
+    pub fn new(scale: f32, even: impl Into<Textures>, odd: impl Into<Textures>) -> Self {
+        Self {
+            inverse_scale: 1. / scale,
+            even: Box::new(even.into()),
+            odd: Box::new(odd.into()),
+        }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f32, v: f32, p: Point3) -> Color {
+        let cell = |c: f32| (c * self.inverse_scale).floor() as i64;
+
+        if (cell(p.x()) + cell(p.y()) + cell(p.z())) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// What image textures do with coordinates outside [0, 1].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl Wrap {
+    pub const NAMES: &'static str = "`repeat`, `clamp` or `mirror`";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "repeat" => Some(Wrap::Repeat),
+            "clamp" => Some(Wrap::Clamp),
+            "mirror" => Some(Wrap::Mirror),
+            _ => None,
+        }
+    }
+
+    /// Maps a texel index onto `0..len`.
+    fn apply(self, i: i64, len: usize) -> usize {
+        let len = len as i64;
+
+        let i = match self {
+            Wrap::Repeat => i.rem_euclid(len),
+            Wrap::Clamp => i.clamp(0, len - 1),
+            Wrap::Mirror => {
+                let i = i.rem_euclid(2 * len);
+                if i < len {
+                    i
+                } else {
+                    2 * len - 1 - i
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+/// Image mapped over `(u, v)` in [0, 1], with `v` going up, bilinearly filtered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageTexture {
+    image: Arc<Image>,
+    wrap: Wrap,
+}
+
+impl ImageTexture {
+    pub fn new(image: Arc<Image>, wrap: Wrap) -> Self {
+        Self { image, wrap }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f32, v: f32, _p: Point3) -> Color {
+        let (width, height) = (self.image.width, self.image.height);
+
+        // Texel centers sit at half-integer coordinates.
+        let x = u * width as f32 - 0.5;
+        let y = (1. - v) * height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+
+        let texel = |dx: i64, dy: i64| {
+            self.image.pixel(
+                self.wrap.apply(x0 as i64 + dx, width),
+                self.wrap.apply(y0 as i64 + dy, height),
+            )
+        };
+
+        let top = texel(0, 0) * (1. - tx) + texel(1, 0) * tx;
+        let bottom = texel(0, 1) * (1. - tx) + texel(1, 1) * tx;
+        top * (1. - ty) + bottom * ty
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseStyle {
+    /// Smooth noise.
+    Perlin,
+    /// Several octaves of noise.
+    Turbulence,
+    /// Stripes along `z`, with their phase shifted by turbulence.
+    Marble,
+}
+
+impl NoiseStyle {
+    pub const NAMES: &'static str = "`perlin`, `turbulence` or `marble`";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "perlin" => Some(NoiseStyle::Perlin),
+            "turbulence" => Some(NoiseStyle::Turbulence),
+            "marble" => Some(NoiseStyle::Marble),
+            _ => None,
+        }
+    }
+}
+
+/// Solid texture scaling `color` by Perlin noise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Noise {
+    perlin: Arc<Perlin>,
+    style: NoiseStyle,
+    /// Frequency of the noise.
+    scale: f32,
+    color: Color,
+}
+
+impl Noise {
+    /// Octaves of turbulence.
+    const DEPTH: u32 = 7;
+
+    pub fn new(perlin: Arc<Perlin>, style: NoiseStyle, scale: f32, color: Color) -> Self {
+        Self {
+            perlin,
+            style,
+            scale,
+            color,
+        }
+    }
+}
+
+impl Texture for Noise {
+    fn value(&self, _u: f32, _v: f32, p: Point3) -> Color {
+        let intensity = match self.style {
+            NoiseStyle::Perlin => 0.5 * (1. + self.perlin.noise(p * self.scale)),
+            NoiseStyle::Turbulence => self.perlin.turbulence(p * self.scale, Self::DEPTH),
+            NoiseStyle::Marble => {
+                0.5 * (1.
+                    + (self.scale * p.z() + 10. * self.perlin.turbulence(p, Self::DEPTH)).sin())
+            }
+        };
+
+        self.color * intensity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Checker, ImageTexture, Noise, NoiseStyle, Texture, Wrap};
+    use crate::{image::Image, perlin::Perlin, ray::Point3, vec3::Color};
+    use std::sync::Arc;
+
+    #[test]
+    fn checker() {
+        let black = Color::new(0., 0., 0.);
+        let white = Color::new(1., 1., 1.);
+        let checker = Checker::new(0.5, white, black);
+
+        assert_eq!(checker.value(0., 0., Point3::new(0.1, 0.1, 0.1)), white);
+        assert_eq!(checker.value(0., 0., Point3::new(0.6, 0.1, 0.1)), black);
+        assert_eq!(checker.value(0., 0., Point3::new(-0.1, 0.1, 0.1)), black);
+        assert_eq!(checker.value(0., 0., Point3::new(0.6, -0.1, 0.1)), white);
+    }
+
+    #[test]
+    fn image_wrap_and_filtering() {
+        // Two texels, black then white (255 is 1 in linear as well).
+        let image = Image::decode(b"P3 2 1 255 0 0 0 255 255 255").unwrap();
+        let p = Point3::default();
+        let texture = |wrap| ImageTexture::new(Arc::new(image.clone()), wrap);
+
+        // Texel centers and halfway between them.
+        let repeat = texture(Wrap::Repeat);
+        assert_eq!(repeat.value(0.25, 0.5, p).x(), 0.);
+        assert_eq!(repeat.value(0.75, 0.5, p).x(), 1.);
+        assert_eq!(repeat.value(0.5, 0.5, p).x(), 0.5);
+        // Filtering across the edge blends with the texel on the other side.
+        assert_eq!(repeat.value(1., 0.5, p).x(), 0.5);
+        assert_eq!(repeat.value(1.25, 0.5, p).x(), 0.);
+
+        let clamp = texture(Wrap::Clamp);
+        assert_eq!(clamp.value(1., 0.5, p).x(), 1.);
+        assert_eq!(clamp.value(-3., 0.5, p).x(), 0.);
+
+        let mirror = texture(Wrap::Mirror);
+        assert_eq!(mirror.value(1.25, 0.5, p).x(), 1.);
+        assert_eq!(mirror.value(1.75, 0.5, p).x(), 0.);
+    }
+
+    #[test]
+    fn noise_is_smooth_and_bounded() {
+        let perlin = Arc::new(Perlin::new(1));
+        let noise = Noise::new(
+            perlin.clone(),
+            NoiseStyle::Perlin,
+            4.,
+            Color::new(1., 1., 1.),
+        );
+
+        for i in 0..1000 {
+            let p = Point3::new(i as f32 * 0.37, i as f32 * -0.11, i as f32 * 0.05);
+            let c = noise.value(0., 0., p).x();
+            assert!((0. ..=1.).contains(&c));
+
+            let nearby = noise.value(0., 0., p + Point3::new(1e-3, 0., 0.)).x();
+            assert!((c - nearby).abs() < 0.05);
+        }
+
+        assert_eq!(perlin.noise(Point3::new(1., 2., 3.)), 0.);
+        assert_eq!(Perlin::new(1), *perlin);
+    }
+}
@@ -0,0 +1,295 @@
+//! A minimal parser for the subset of TOML used by scene files.
+//!
+//! Supported: `# comments`, `[table]` and `[[array-of-tables]]` headers, and `key = value`
+//! pairs whose value is a string, a number, a boolean or a single-line array of values.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Number(f32),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Number(_) => "number",
+            Value::Bool(_) => "boolean",
+            Value::Array(_) => "array",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    /// Header name, empty for the root table.
+    pub name: String,
+    /// Line of the header, 0 for the root table.
+    pub line: usize,
+    pub entries: Vec<Entry>,
+}
+
+/// Every table of the document in file order, starting with the (possibly empty) root table.
+/// Each `[[name]]` header opens a new table with the same name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub tables: Vec<Table>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn parse(src: &str) -> Result<Document, ParseError> {
+    let mut tables = vec![Table {
+        name: String::new(),
+        line: 0,
+        entries: Vec::new(),
+    }];
+    // Plain `[table]` names already seen, they may only appear once.
+    let mut seen: Vec<String> = Vec::new();
+
+    for (i, raw) in src.lines().enumerate() {
+        let line = i + 1;
+        let err = |message: String| ParseError { line, message };
+
+        let mut cursor = Cursor::new(raw);
+        cursor.skip_ws();
+
+        if cursor.at_end() {
+            continue;
+        }
+
+        if cursor.eat('[') {
+            let array = cursor.eat('[');
+            let name = cursor.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if name.is_empty() {
+                return Err(err("expected a table name".into()));
+            }
+            if !cursor.eat(']') || (array && !cursor.eat(']')) {
+                return Err(err(format!("unterminated header for table `{name}`")));
+            }
+            cursor.expect_end().map_err(err)?;
+
+            if !array {
+                if seen.iter().any(|s| *s == name) {
+                    return Err(err(format!("table `[{name}]` defined twice")));
+                }
+                seen.push(name.to_owned());
+            }
+
+            tables.push(Table {
+                name: name.to_owned(),
+                line,
+                entries: Vec::new(),
+            });
+            continue;
+        }
+
+        let key = cursor.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if key.is_empty() {
+            return Err(err("expected a key or a table header".into()));
+        }
+        cursor.skip_ws();
+        if !cursor.eat('=') {
+            return Err(err(format!("expected `=` after key `{key}`")));
+        }
+
+        let value = cursor
+            .value()
+            .map_err(|e| err(format!("field `{key}`: {e}")))?;
+        cursor.expect_end().map_err(err)?;
+
+        let table = tables.last_mut().unwrap();
+        if table.entries.iter().any(|e| e.key == key) {
+            return Err(err(format!("field `{key}` defined twice")));
+        }
+
+        table.entries.push(Entry {
+            key: key.to_owned(),
+            value,
+            line,
+        });
+    }
+
+    Ok(Document { tables })
+}
+
+struct Cursor<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(src: &'a str) -> Self {
+        Self { src, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// True at the end of the line or at the start of a comment.
+    fn at_end(&self) -> bool {
+        matches!(self.peek(), None | Some('#'))
+    }
+
+    fn expect_end(&mut self) -> Result<(), String> {
+        self.skip_ws();
+        if self.at_end() {
+            Ok(())
+        } else {
+            Err(format!("unexpected `{}`", self.rest().trim_end()))
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_ws();
+
+        match self.peek() {
+            Some('"') => {
+                self.pos += 1;
+                let s = self.take_while(|c| c != '"');
+                if !self.eat('"') {
+                    return Err("unterminated string".into());
+                }
+                Ok(Value::String(s.to_owned()))
+            }
+            Some('[') => {
+                self.pos += 1;
+                let mut values = Vec::new();
+                loop {
+                    self.skip_ws();
+                    if self.eat(']') {
+                        return Ok(Value::Array(values));
+                    }
+                    values.push(self.value()?);
+                    self.skip_ws();
+                    if !self.eat(',') {
+                        self.skip_ws();
+                        if self.eat(']') {
+                            return Ok(Value::Array(values));
+                        }
+                        return Err("expected `,` or `]` in array".into());
+                    }
+                }
+            }
+            Some(_) => {
+                let word = self.take_while(|c| {
+                    c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_')
+                });
+
+                match word {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    "" => Err(format!("unexpected `{}`", self.rest().trim_end())),
+                    _ => word
+                        .replace('_', "")
+                        .parse()
+                        .map(Value::Number)
+                        .map_err(|_| format!("invalid value `{word}`")),
+                }
+            }
+            None => Err("missing value".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Value};
+
+    #[test]
+    fn tables_and_values() {
+        let doc = parse(
+            r#"
+            title = "demo" # trailing comment
+            [camera]
+            vfov = 20
+            lookfrom = [13, 2.5, -3e-1]
+
+            [[sphere]]
+            visible = true
+            [[sphere]]
+            tags = ["a", "b",]
+            "#,
+        )
+        .unwrap();
+
+        let names: Vec<_> = doc.tables.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["", "camera", "sphere", "sphere"]);
+
+        assert_eq!(doc.tables[0].entries[0].value, Value::String("demo".into()));
+        assert_eq!(doc.tables[1].line, 3);
+        assert_eq!(doc.tables[1].entries[0].value, Value::Number(20.));
+        assert_eq!(
+            doc.tables[1].entries[1].value,
+            Value::Array(vec![
+                Value::Number(13.),
+                Value::Number(2.5),
+                Value::Number(-0.3)
+            ])
+        );
+        assert_eq!(doc.tables[2].entries[0].value, Value::Bool(true));
+        assert_eq!(doc.tables[3].entries[0].line, 10);
+    }
+
+    #[test]
+    fn errors_report_line() {
+        let err = parse("[camera]\nvfov = 2x0\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.contains("vfov"));
+
+        let err = parse("[camera]\n[camera]\n").unwrap_err();
+        assert_eq!(err.line, 2);
+
+        let err = parse("a = [1, 2\n").unwrap_err();
+        assert_eq!(err.line, 1);
+    }
+}
@@ -0,0 +1,489 @@
+//! Affine transforms, and instances that place a shared object in the scene with one.
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable, Primitives},
+    material::Materials,
+    ray::{Point3, Ray},
+    vec3::Vec3,
+};
+
+use std::sync::Arc;
+
+use fastrand::Rng;
+
+type Matrix = [[f32; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1., 0., 0., 0.],
+    [0., 1., 0., 0.],
+    [0., 0., 1., 0.],
+    [0., 0., 0., 1.],
+];
+
+/// Affine transform, kept along with its inverse so neither has to be computed per ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    matrix: Matrix,
+    inverse: Matrix,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        matrix: IDENTITY,
+        inverse: IDENTITY,
+    };
+
+    pub fn translate(offset: Vec3) -> Self {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for i in 0..3 {
+            matrix[i][3] = offset[i];
+            inverse[i][3] = -offset[i];
+        }
+
+        Self { matrix, inverse }
+    }
+
+    /// Scales by a factor per axis, none of which may be zero.
+    pub fn scale(factors: Vec3) -> Self {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for i in 0..3 {
+            matrix[i][i] = factors[i];
+            inverse[i][i] = 1. / factors[i];
+        }
+
+        Self { matrix, inverse }
+    }
+
+    /// Counterclockwise rotation around `axis`, looking down from its tip.
+    pub fn rotate(axis: Vec3, degrees: f32) -> Self {
+        let a = axis.unit_vector();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let k = 1. - cos;
+
+        // Rodrigues' rotation formula.
+        let mut matrix = IDENTITY;
+        matrix[0][..3].copy_from_slice(&[
+            cos + a.x() * a.x() * k,
+            a.x() * a.y() * k - a.z() * sin,
+            a.x() * a.z() * k + a.y() * sin,
+        ]);
+        matrix[1][..3].copy_from_slice(&[
+            a.y() * a.x() * k + a.z() * sin,
+            cos + a.y() * a.y() * k,
+            a.y() * a.z() * k - a.x() * sin,
+        ]);
+        matrix[2][..3].copy_from_slice(&[
+            a.z() * a.x() * k - a.y() * sin,
+            a.z() * a.y() * k + a.x() * sin,
+            cos + a.z() * a.z() * k,
+        ]);
+
+        Self {
+            matrix,
+            inverse: transpose(&matrix),
+        }
+    }
+
+    /// This transform followed by `next`.
+    pub fn then(&self, next: &Transform) -> Self {
+        Self {
+            matrix: multiply(&next.matrix, &self.matrix),
+            inverse: multiply(&self.inverse, &next.inverse),
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        let m = &self.matrix;
+        let row = |i: usize| m[i][0] * p.x() + m[i][1] * p.y() + m[i][2] * p.z() + m[i][3];
+
+        Point3::new(row(0), row(1), row(2))
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.matrix;
+        let row = |i: usize| m[i][0] * v.x() + m[i][1] * v.y() + m[i][2] * v.z();
+
+        Vec3::new(row(0), row(1), row(2))
+    }
+
+    /// Normals stay perpendicular to transformed surfaces under the inverse transpose. The
+    /// result isn't normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let m = &self.inverse;
+        let column = |i: usize| m[0][i] * n.x() + m[1][i] * n.y() + m[2][i] * n.z();
+
+        Vec3::new(column(0), column(1), column(2))
+    }
+
+    /// Box around the transformed corners of `b`.
+    pub fn bounds(&self, b: &Aabb) -> Aabb {
+        (0..8).fold(Aabb::EMPTY, |acc, corner| {
+            let pick = |axis: usize| {
+                if corner & (1 << axis) == 0 {
+                    b.min[axis]
+                } else {
+                    b.max[axis]
+                }
+            };
+
+            acc.grow(self.point(Point3::new(pick(0), pick(1), pick(2))))
+        })
+    }
+
+    /// Determinant of the linear part, how much volumes are scaled by.
+    fn determinant(&self) -> f32 {
+        let m = &self.matrix;
+
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0.; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+
+    m
+}
+
+fn transpose(a: &Matrix) -> Matrix {
+    let mut m = [[0.; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = a[j][i];
+        }
+    }
+
+    m
+}
+
+/// Scale, then rotation in degrees around the x, y and z axes, then translation. Unlike
+/// matrices, these can be interpolated, and turns of more than half a circle survive it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    pub scale: Vec3,
+    pub rotate: Vec3,
+    pub translate: Vec3,
+}
+
+impl Default for Pose {
+    fn default() -> Self {
+        Self {
+            scale: Vec3::splat(1.),
+            rotate: Vec3::default(),
+            translate: Vec3::default(),
+        }
+    }
+}
+
+impl Pose {
+    pub fn transform(&self) -> Transform {
+        Transform::scale(self.scale)
+            .then(&Transform::rotate(Vec3::new(1., 0., 0.), self.rotate.x()))
+            .then(&Transform::rotate(Vec3::new(0., 1., 0.), self.rotate.y()))
+            .then(&Transform::rotate(Vec3::new(0., 0., 1.), self.rotate.z()))
+            .then(&Transform::translate(self.translate))
+    }
+
+    fn lerp(&self, other: &Pose, t: f32) -> Pose {
+        let mix = |a: Vec3, b: Vec3| a + (b - a) * t;
+
+        Pose {
+            scale: mix(self.scale, other.scale),
+            rotate: mix(self.rotate, other.rotate),
+            translate: mix(self.translate, other.translate),
+        }
+    }
+}
+
+/// An object placed with a transform, and optionally a different material. The object is
+/// shared, so one mesh can be placed many times without copying its triangles or BVH.
+#[derive(Clone)]
+pub struct Instance {
+    object: Arc<Primitives>,
+    transform: Transform,
+    /// Poses at times 0 and 1, for objects that move.
+    animation: Option<Box<(Pose, Pose)>>,
+    material: Option<Materials>,
+}
+
+impl Instance {
+    pub fn new(object: Arc<Primitives>, transform: Transform, material: Option<Materials>) -> Self {
+        Self {
+            object,
+            transform,
+            animation: None,
+            material,
+        }
+    }
+
+    /// Instance moving from `start` at time 0 to `end` at time 1, and resting at either end
+    /// outside that interval.
+    pub fn animated(
+        object: Arc<Primitives>,
+        start: Pose,
+        end: Pose,
+        material: Option<Materials>,
+    ) -> Self {
+        Self {
+            animation: (start != end).then(|| Box::new((start, end))),
+            ..Self::new(object, start.transform(), material)
+        }
+    }
+
+    pub fn material(&self) -> &Materials {
+        self.material
+            .as_ref()
+            .unwrap_or_else(|| self.object.material())
+    }
+
+    fn transform(&self, time: f32) -> Transform {
+        match &self.animation {
+            Some(animation) => animation
+                .0
+                .lerp(&animation.1, time.clamp(0., 1.))
+                .transform(),
+            None => self.transform,
+        }
+    }
+
+    /// The transform at the ray's time, and the ray moved into object space without normalizing
+    /// its direction, so distances along it are the same in both spaces.
+    fn to_object(&self, r: &Ray) -> (Transform, Ray) {
+        let transform = self.transform(r.time);
+        let to_object = transform.inverse();
+        let local = Ray::with_time(
+            to_object.point(r.origin),
+            to_object.vector(r.direction),
+            r.time,
+        );
+
+        (transform, local)
+    }
+
+    /// Moves a hit found in object space back to the world.
+    fn to_world<'a>(&'a self, transform: &Transform, mut hit: HitRecord<'a>) -> HitRecord<'a> {
+        hit.point = transform.point(hit.point);
+        hit.normal = transform.normal(hit.normal).unit_vector();
+        hit.dpdu = transform.vector(hit.dpdu);
+        hit.dpdv = transform.vector(hit.dpdv);
+        if let Some(material) = &self.material {
+            hit.material = material;
+        }
+
+        hit
+    }
+}
+
+impl Hittable for Instance {
+    /// The ray is moved into object space without normalizing its direction, so distances
+    /// along it are the same in both spaces.
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &Rng) -> Option<HitRecord<'_>> {
+        let (transform, local) = self.to_object(r);
+        let hit = self.object.hit(&local, t_min, t_max, rng)?;
+
+        Some(self.to_world(&transform, hit))
+    }
+
+    fn hit_surface(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (transform, local) = self.to_object(r);
+        let hit = self.object.hit_surface(&local, t_min, t_max)?;
+
+        Some(self.to_world(&transform, hit))
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32, rng: &Rng) -> f32 {
+        self.object
+            .transmittance(&self.to_object(r).1, t_min, t_max, rng)
+    }
+
+    /// Moving instances are bounded at evenly spaced times. Between two of them no point moves
+    /// further than its top speed times half the step, so the boxes are grown by that much.
+    fn bounding_box(&self) -> Option<Aabb> {
+        let b = self.object.bounding_box()?;
+        let Some(animation) = &self.animation else {
+            return Some(self.transform.bounds(&b));
+        };
+
+        const STEPS: usize = 64;
+        let (start, end) = (&animation.0, &animation.1);
+        let bounds = (0..=STEPS).fold(Aabb::EMPTY, |acc, i| {
+            let t = i as f32 / STEPS as f32;
+            acc.union(&start.lerp(end, t).transform().bounds(&b))
+        });
+
+        let far = |axis: usize| b.min[axis].abs().max(b.max[axis].abs());
+        let reach = Vec3::new(far(0), far(1), far(2)).len();
+        let largest = |v: Vec3| v.x().abs().max(v.y().abs()).max(v.z().abs());
+        let turn = end.rotate - start.rotate;
+        let angular_speed = (turn.x().abs() + turn.y().abs() + turn.z().abs()).to_radians();
+        let speed = (end.translate - start.translate).len()
+            + reach
+                * (angular_speed * largest(start.scale).max(largest(end.scale))
+                    + largest(end.scale - start.scale));
+        let grow = Vec3::splat(speed / (2 * STEPS) as f32);
+
+        Some(Aabb::new(bounds.min - grow, bounds.max + grow))
+    }
+
+    /// Directions map to object space through the inverse transform, which stretches solid
+    /// angles by `|det| / |inverse · direction|^3` for a unit direction.
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f32) -> f32 {
+        let to_object = self.transform(time).inverse();
+        let local = to_object.vector(direction.unit_vector());
+
+        self.object.pdf_value(to_object.point(origin), local, time) * to_object.determinant().abs()
+            / local.len().powi(3)
+    }
+
+    fn random(&self, origin: Point3, time: f32, u: (f32, f32)) -> Vec3 {
+        let transform = self.transform(time);
+        let local_origin = transform.inverse().point(origin);
+
+        transform.vector(self.object.random(local_origin, time, u))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Instance, Pose, Transform};
+    use crate::{
+        hittable::Hittable,
+        material::{DiffuseLight, Lambertian, Materials},
+        quad::Cuboid,
+        ray::{Point3, Ray},
+        sampler::uniform_sphere,
+        sphere::Sphere,
+        vec3::{Color, Vec3},
+    };
+    use std::{f32::consts::PI, sync::Arc};
+
+    use fastrand::Rng;
+
+    fn transform() -> Transform {
+        Transform::scale(Vec3::new(2., 1., 0.5))
+            .then(&Transform::rotate(Vec3::new(1., 1., 0.), 30.))
+            .then(&Transform::translate(Vec3::new(1., -2., 3.)))
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let t = transform();
+        let p = Point3::new(0.3, -4., 2.5);
+
+        assert!((t.inverse().point(t.point(p)) - p).len() < 1e-5);
+        assert!((t.point(p) - Point3::new(1., -2., 3.) - t.vector(p)).len() < 1e-5);
+
+        let quarter = Transform::rotate(Vec3::new(0., 1., 0.), 90.);
+        assert!((quarter.vector(Vec3::new(1., 0., 0.)) - Vec3::new(0., 0., -1.)).len() < 1e-6);
+
+        // Normals stay perpendicular to transformed tangents.
+        let (tangent, normal) = (Vec3::new(1., 2., 0.), Vec3::new(-2., 1., 3.));
+        assert!(t.vector(tangent).dot(t.normal(normal)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn instanced_ellipsoid() {
+        let rng = Rng::with_seed(1);
+        let sphere = Sphere::new(
+            Point3::default(),
+            1.,
+            Materials::Lambertian(Lambertian::default()),
+        );
+        let lamp = Materials::DiffuseLight(DiffuseLight::new(Color::new(1., 1., 1.)));
+        let instance = Instance::new(Arc::new(sphere.into()), transform(), Some(lamp.clone()));
+        let t = transform();
+
+        // The point of the sphere closest to the origin in object space is in sight.
+        let origin = Point3::new(5., 4., -3.);
+        let target = t.point(t.inverse().point(origin).unit_vector());
+        let hit = instance
+            .hit_surface(&Ray::new(origin, target - origin), 0.001, f32::INFINITY)
+            .unwrap();
+        assert!((hit.point - target).len() < 1e-4);
+        assert!(hit.front_face && *hit.material == lamp);
+        assert!(hit.normal.dot(hit.dpdu).abs() < 1e-4 && hit.normal.dot(hit.dpdv).abs() < 1e-4);
+
+        let bounds = instance.bounding_box().unwrap();
+        assert!(target.min(bounds.min) == bounds.min && target.max(bounds.max) == bounds.max);
+
+        let origin = t.point(Point3::new(0.5, 0., 1.5));
+        let n = 200_000;
+        let integral = (0..n)
+            .map(|_| instance.pdf_value(origin, uniform_sphere((rng.f32(), rng.f32())), 0.))
+            .sum::<f32>()
+            * 4.
+            * PI
+            / n as f32;
+        assert!((integral - 1.).abs() < 0.03, "{integral}");
+
+        let direction = instance.random(origin, 0., (rng.f32(), rng.f32()));
+        assert!(instance.pdf_value(origin, direction, 0.) > 0.);
+    }
+
+    #[test]
+    fn animated_bounds_cover_the_motion() {
+        let white = Materials::Lambertian(Lambertian::default());
+        let cube = Cuboid::new(Point3::new(-1., 0., -1.), Point3::new(1., 1., 1.), white);
+        let start = Pose {
+            translate: Vec3::new(0., 0., 3.),
+            ..Pose::default()
+        };
+        let end = Pose {
+            scale: Vec3::new(1., 2., 1.),
+            rotate: Vec3::new(30., 360., 0.),
+            translate: Vec3::new(4., 0., 3.),
+        };
+        let instance = Instance::animated(Arc::new(cube.into()), start, end, None);
+        let bounds = instance.bounding_box().unwrap();
+
+        for i in 0..=1000 {
+            let time = i as f32 / 1000.;
+            let transform = start.lerp(&end, time).transform();
+            for corner in 0..8 {
+                let pick = |axis: usize, min: f32, max: f32| {
+                    if corner & (1 << axis) == 0 {
+                        min
+                    } else {
+                        max
+                    }
+                };
+                let p = transform.point(Point3::new(
+                    pick(0, -1., 1.),
+                    pick(1, 0., 1.),
+                    pick(2, -1., 1.),
+                ));
+                assert!(p.min(bounds.min) == bounds.min && p.max(bounds.max) == bounds.max);
+            }
+        }
+
+        // Halfway through, the box has moved by half the distance.
+        let r = Ray::with_time(Point3::new(2., 0.5, 10.), Vec3::new(0., 0., -1.), 0.5);
+        let hit = instance.hit_surface(&r, 0.001, f32::INFINITY).unwrap();
+        assert!(hit.point.z() < 5. && hit.point.z() > 3.);
+        assert!(instance
+            .hit_surface(&Ray::new(r.origin, r.direction), 0.001, f32::INFINITY)
+            .is_none());
+    }
+}
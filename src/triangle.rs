@@ -0,0 +1,384 @@
+use crate::{
+    aabb::Aabb,
+    bvh::Bvh,
+    hittable::{HitRecord, Hittable},
+    material::Materials,
+    onb::Onb,
+    ray::{Point3, Ray},
+    vec3::Vec3,
+};
+
+use std::sync::Arc;
+
+/// Vertex data shared by all the triangles of a mesh. Normals and texture coordinates are either
+/// empty or hold one entry per position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f32, f32)>,
+    pub indices: Vec<[u32; 3]>,
+    pub material: Materials,
+}
+
+/// One triangle of a mesh.
+#[derive(Debug, Clone)]
+pub struct Triangle {
+    mesh: Arc<MeshData>,
+    index: usize,
+}
+
+/// Indexed triangle mesh, with its own BVH over the triangles.
+#[derive(Clone)]
+pub struct TriangleMesh {
+    triangles: Bvh<Triangle>,
+    mesh: Arc<MeshData>,
+    /// Running sum of the triangle areas, to sample points uniformly over the surface.
+    cumulative_area: Vec<f32>,
+}
+
+impl TriangleMesh {
+    pub fn new(data: MeshData) -> Self {
+        debug_assert!(data.normals.is_empty() || data.normals.len() == data.positions.len());
+        debug_assert!(data.uvs.is_empty() || data.uvs.len() == data.positions.len());
+
+        let mesh = Arc::new(data);
+        let triangles = (0..mesh.indices.len())
+            .map(|index| Triangle {
+                mesh: mesh.clone(),
+                index,
+            })
+            .collect::<Vec<_>>();
+
+        let mut area = 0.;
+        let cumulative_area = triangles
+            .iter()
+            .map(|t| {
+                area += t.area();
+                area
+            })
+            .collect();
+
+        Self {
+            triangles: Bvh::new(triangles),
+            mesh,
+            cumulative_area,
+        }
+    }
+
+    pub fn material(&self) -> &Materials {
+        &self.mesh.material
+    }
+
+    fn area(&self) -> f32 {
+        self.cumulative_area.last().copied().unwrap_or(0.)
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit_surface(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.triangles.hit_surface(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.triangles.bounding_box()
+    }
+
+    /// Points are sampled uniformly by area, so every crossing of the surface along `direction`
+    /// adds `distance^2 / (cos * area)`.
+    fn pdf_value(&self, origin: Point3, direction: Vec3, _time: f32) -> f32 {
+        let area = self.area();
+        if area <= 0. {
+            return 0.;
+        }
+
+        let r = Ray::new(origin, direction.unit_vector());
+        let mut pdf = 0.;
+        let mut t_min = 0.001;
+
+        while let Some((hit, triangle)) = self.triangles.closest_surface(&r, t_min, f32::INFINITY) {
+            let cosine = triangle.normal().dot(r.direction).abs();
+            if cosine > 0. {
+                pdf += hit.t * hit.t / (cosine * area);
+            }
+
+            // Step past the hit, so a crossing through a shared edge is only counted once.
+            t_min = hit.t * (1. + 1e-4) + 1e-4;
+        }
+
+        pdf
+    }
+
+    /// The first coordinate picks a triangle by area, then is reused to sample it.
+    fn random(&self, origin: Point3, _time: f32, u: (f32, f32)) -> Vec3 {
+        let target = u.0 * self.area();
+        let index = self
+            .cumulative_area
+            .partition_point(|&a| a <= target)
+            .min(self.cumulative_area.len() - 1);
+        let start = index.checked_sub(1).map_or(0., |i| self.cumulative_area[i]);
+        let u0 = ((target - start) / (self.cumulative_area[index] - start)).clamp(0., 1.);
+
+        let [i0, i1, i2] = self.mesh.indices[index];
+        let p = &self.mesh.positions;
+
+        // Uniform barycentric coordinates.
+        let s = u0.sqrt();
+        let (b0, b1) = (1. - s, u.1 * s);
+        let point = p[i0 as usize] * b0 + p[i1 as usize] * b1 + p[i2 as usize] * (1. - b0 - b1);
+
+        point - origin
+    }
+}
+
+impl Triangle {
+    fn vertices(&self) -> [u32; 3] {
+        self.mesh.indices[self.index]
+    }
+
+    fn edges(&self) -> (Vec3, Vec3) {
+        let [i0, i1, i2] = self.vertices();
+        let p = &self.mesh.positions;
+
+        (
+            p[i1 as usize] - p[i0 as usize],
+            p[i2 as usize] - p[i0 as usize],
+        )
+    }
+
+    /// Geometric normal, following the winding order.
+    fn normal(&self) -> Vec3 {
+        let (e1, e2) = self.edges();
+        e1.cross(e2).unit_vector()
+    }
+
+    fn area(&self) -> f32 {
+        let (e1, e2) = self.edges();
+        e1.cross(e2).len() / 2.
+    }
+}
+
+impl Hittable for Triangle {
+    /// Watertight ray-triangle intersection (Woop, Benthin and Wald, 2013): rays through a shared
+    /// edge or vertex hit at least one of the triangles that meet there.
+    fn hit_surface(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let [i0, i1, i2] = self.vertices();
+        let p = &self.mesh.positions;
+        let (p0, p1, p2) = (p[i0 as usize], p[i1 as usize], p[i2 as usize]);
+
+        // Permute the axes so that z is the dominant direction component, keeping the winding.
+        let d = r.direction;
+        let abs = [d.x().abs(), d.y().abs(), d.z().abs()];
+        let kz = if abs[0] > abs[1] && abs[0] > abs[2] {
+            0
+        } else if abs[1] > abs[2] {
+            1
+        } else {
+            2
+        };
+        let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+        if d[kz] < 0. {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+
+        // Shear so the ray points along +z, starting at the origin.
+        let sx = d[kx] / d[kz];
+        let sy = d[ky] / d[kz];
+        let sz = 1. / d[kz];
+
+        let a = p0 - r.origin;
+        let b = p1 - r.origin;
+        let c = p2 - r.origin;
+
+        let ax = a[kx] - sx * a[kz];
+        let ay = a[ky] - sy * a[kz];
+        let bx = b[kx] - sx * b[kz];
+        let by = b[ky] - sy * b[kz];
+        let cx = c[kx] - sx * c[kz];
+        let cy = c[ky] - sy * c[kz];
+
+        // Scaled barycentric coordinates, recomputed in double precision when on an edge.
+        let (mut u, mut v, mut w) = (cx * by - cy * bx, ax * cy - ay * cx, bx * ay - by * ax);
+        if u == 0. || v == 0. || w == 0. {
+            let (ax, ay, bx, by, cx, cy) = (
+                ax as f64, ay as f64, bx as f64, by as f64, cx as f64, cy as f64,
+            );
+            u = (cx * by - cy * bx) as f32;
+            v = (ax * cy - ay * cx) as f32;
+            w = (bx * ay - by * ax) as f32;
+        }
+
+        if (u < 0. || v < 0. || w < 0.) && (u > 0. || v > 0. || w > 0.) {
+            return None;
+        }
+
+        let det = u + v + w;
+        if det == 0. {
+            return None;
+        }
+
+        let t = (u * sz * a[kz] + v * sz * b[kz] + w * sz * c[kz]) / det;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        let (b0, b1, b2) = (u / det, v / det, w / det);
+        let point = p0 * b0 + p1 * b1 + p2 * b2;
+        let mut hr = HitRecord::new(point, self.normal(), t, &self.mesh.material);
+        hr.set_face_normal(r);
+
+        // Interpolated texture coordinates, or the barycentric ones when the mesh has none.
+        let (uv0, uv1, uv2) = if self.mesh.uvs.is_empty() {
+            ((0., 0.), (1., 0.), (0., 1.))
+        } else {
+            let uv = &self.mesh.uvs;
+            (uv[i0 as usize], uv[i1 as usize], uv[i2 as usize])
+        };
+        hr.u = uv0.0 * b0 + uv1.0 * b1 + uv2.0 * b2;
+        hr.v = uv0.1 * b0 + uv1.1 * b1 + uv2.1 * b2;
+
+        // The edges in terms of dpdu and dpdv: dp02 = du02 * dpdu + dv02 * dpdv, and so on.
+        let (du02, dv02) = (uv0.0 - uv2.0, uv0.1 - uv2.1);
+        let (du12, dv12) = (uv1.0 - uv2.0, uv1.1 - uv2.1);
+        let (dp02, dp12) = (p0 - p2, p1 - p2);
+        let det = du02 * dv12 - dv02 * du12;
+
+        (hr.dpdu, hr.dpdv) = if det.abs() > 1e-12 {
+            (
+                (dp02 * dv12 - dp12 * dv02) / det,
+                (dp12 * du02 - dp02 * du12) / det,
+            )
+        } else {
+            // Degenerate texture coordinates, any tangents will do.
+            let frame = Onb::from_w(self.normal());
+            (frame.u, frame.v)
+        };
+
+        // Smooth shading, kept on the side of the surface the ray arrived from.
+        if !self.mesh.normals.is_empty() {
+            let n = &self.mesh.normals;
+            let shading_normal =
+                (n[i0 as usize] * b0 + n[i1 as usize] * b1 + n[i2 as usize] * b2).unit_vector();
+
+            hr.normal = if shading_normal.dot(hr.normal) < 0. {
+                -shading_normal
+            } else {
+                shading_normal
+            };
+        }
+
+        Some(hr)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let [i0, i1, i2] = self.vertices();
+        let p = &self.mesh.positions;
+
+        Some(Aabb::new(p[i0 as usize], p[i1 as usize]).grow(p[i2 as usize]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MeshData, TriangleMesh};
+    use crate::{
+        hittable::Hittable,
+        material::{Lambertian, Materials},
+        ray::{Point3, Ray},
+        sampler::uniform_sphere,
+        vec3::Vec3,
+    };
+
+    use fastrand::Rng;
+
+    fn quad() -> MeshData {
+        MeshData {
+            positions: vec![
+                Point3::new(-1., -1., 0.),
+                Point3::new(1., -1., 0.),
+                Point3::new(1., 1., 0.),
+                Point3::new(-1., 1., 0.),
+            ],
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+            material: Materials::Lambertian(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        }
+    }
+
+    #[test]
+    fn watertight_shared_edge() {
+        let mesh = TriangleMesh::new(quad());
+        assert_eq!(mesh.triangles.stats().primitives, 2);
+
+        // Rays through the diagonal shared by both triangles, from awkward origins.
+        for i in 0..1000 {
+            let s = i as f32 / 1000. * 2. - 1.;
+            let target = Point3::new(s, s, 0.);
+            let origin = Point3::new(0.3, -0.7, 3.1) * (1. + i as f32 * 0.01);
+            let r = Ray::new(origin, target - origin);
+
+            let hit = mesh.hit_surface(&r, 0.001, f32::INFINITY);
+            assert!(hit.is_some(), "ray {i} leaked through the edge");
+            assert!((hit.unwrap().t - 1.).abs() < 1e-4);
+        }
+
+        let r = Ray::new(Point3::new(1.5, 0., 1.), Vec3::new(0., 0., -1.));
+        assert!(mesh.hit_surface(&r, 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn smooth_normals_face_the_ray() {
+        let mut data = quad();
+        data.normals = vec![Vec3::new(0., 0., 1.); 4];
+        data.normals[2] = Vec3::new(0., 1., 1.).unit_vector();
+        let mesh = TriangleMesh::new(data);
+
+        let r = Ray::new(Point3::new(0.5, 0.5, -1.), Vec3::new(0., 0., 1.));
+        let hit = mesh.hit_surface(&r, 0.001, f32::INFINITY).unwrap();
+
+        assert!(!hit.front_face);
+        assert!(hit.normal.dot(r.direction) < 0.);
+        assert!(hit.normal.y() < 0.);
+        assert!((hit.normal.len() - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn texture_coordinates_and_tangents() {
+        let mut data = quad();
+        data.uvs = vec![(0., 0.), (1., 0.), (1., 1.), (0., 1.)];
+        let mesh = TriangleMesh::new(data);
+
+        for (x, y) in [(0.5, 0.5), (-0.5, 0.25)] {
+            let r = Ray::new(Point3::new(x, y, 1.), Vec3::new(0., 0., -1.));
+            let hit = mesh.hit_surface(&r, 0.001, f32::INFINITY).unwrap();
+
+            assert!((hit.u - (x + 1.) / 2.).abs() < 1e-5);
+            assert!((hit.v - (y + 1.) / 2.).abs() < 1e-5);
+            assert!((hit.dpdu - Vec3::new(2., 0., 0.)).len() < 1e-5);
+            assert!((hit.dpdv - Vec3::new(0., 2., 0.)).len() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn light_pdf_integrates_to_one() {
+        let rng = Rng::with_seed(1);
+        let mesh = TriangleMesh::new(quad());
+        let origin = Point3::new(0.2, 0.4, 1.);
+
+        // Monte Carlo integral over the sphere of directions, and the density of a sample.
+        let n = 200_000;
+        let integral: f32 = (0..n)
+            .map(|_| mesh.pdf_value(origin, uniform_sphere((rng.f32(), rng.f32())), 0.))
+            .sum::<f32>()
+            * 4.
+            * std::f32::consts::PI
+            / n as f32;
+        assert!((integral - 1.).abs() < 0.03, "{integral}");
+
+        let direction = mesh.random(origin, 0., (rng.f32(), rng.f32()));
+        let target = origin + direction;
+        assert!(target.z().abs() < 1e-5 && target.x().abs() <= 1. && target.y().abs() <= 1.);
+        assert!(mesh.pdf_value(origin, direction, 0.) > 0.);
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign},
+    ops::{Add, AddAssign, Div, Index, Mul, Neg, Sub, SubAssign},
     simd::{Simd, SimdFloat, StdFloat},
 };
 
@@ -19,9 +19,13 @@ impl Vec3 {
         }
     }
 
-    pub fn random() -> Self {
-        let rng = fastrand::Rng::new();
+    pub const fn splat(s: f32) -> Self {
+        Self {
+            v: Simd::from_array([s, s, s, 0.]),
+        }
+    }
 
+    pub fn random(rng: &Rng) -> Self {
         let x = rng.f32();
         let y = rng.f32();
         let z = rng.f32();
@@ -31,29 +35,16 @@ impl Vec3 {
         }
     }
 
-    pub fn random_in_range(min: f32, max: f32) -> Self {
-        let rng = fastrand::Rng::new();
-
-        let x = random_f32(min, max, &rng);
-        let y = random_f32(min, max, &rng);
-        let z = random_f32(min, max, &rng);
+    pub fn random_in_range(min: f32, max: f32, rng: &Rng) -> Self {
+        let x = random_f32(min, max, rng);
+        let y = random_f32(min, max, rng);
+        let z = random_f32(min, max, rng);
 
         Self {
             v: Simd::from([x, y, z, 0.]),
         }
     }
 
-    pub fn random_in_unit_disk() -> Self {
-        let rng = fastrand::Rng::new();
-
-        loop {
-            let p = Vec3::new(random_f32(-1., 1., &rng), random_f32(-1., 1., &rng), 0.);
-            if p.len_squared() < 1. {
-                return p;
-            }
-        }
-    }
-
     #[inline]
     pub fn x(&self) -> f32 {
         self.v[0]
@@ -111,10 +102,41 @@ impl Vec3 {
         }
     }
 
+    /// Component-wise minimum.
+    pub fn min(&self, other: Self) -> Self {
+        Self {
+            v: self.v.simd_min(other.v),
+        }
+    }
+
+    /// Component-wise maximum.
+    pub fn max(&self, other: Self) -> Self {
+        Self {
+            v: self.v.simd_max(other.v),
+        }
+    }
+
+    pub fn max_component(&self) -> f32 {
+        self.x().max(self.y()).max(self.z())
+    }
+
+    pub fn min_component(&self) -> f32 {
+        self.x().min(self.y()).min(self.z())
+    }
+
+    /// Brightness of a linear Rec. 709 color.
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.x() + 0.7152 * self.y() + 0.0722 * self.z()
+    }
+
     pub fn sqrt(&self) -> Self {
         Self { v: self.v.sqrt() }
     }
 
+    pub fn exp(&self) -> Self {
+        Self { v: self.v.exp() }
+    }
+
     pub fn near_zero(&self) -> bool {
         // Return true if the vector is close to zero in all dimensions.
         let s = 1e-8;
@@ -137,6 +159,16 @@ impl Vec3 {
     }
 }
 
+impl Index<usize> for Vec3 {
+    type Output = f32;
+
+    #[inline]
+    fn index(&self, axis: usize) -> &f32 {
+        debug_assert!(axis < 3);
+        &self.v[axis]
+    }
+}
+
 impl Add for Vec3 {
     type Output = Self;
 
@@ -340,6 +372,16 @@ mod tests {
         assert_eq!(a.unit_vector(), Vec3::new(1. / len, -3. / len, 4. / len));
     }
 
+    #[test]
+    fn min_max() {
+        let a = Vec3::new(1., -3., 4.);
+        let b = Vec3::new(2., -5., 4.);
+
+        assert_eq!(a.min(b), Vec3::new(1., -5., 4.));
+        assert_eq!(a.max(b), Vec3::new(2., -3., 4.));
+        assert_eq!(a[1], -3.);
+    }
+
     #[test]
     fn cross() {
         let a = Vec3::new(2., 3., 4.);
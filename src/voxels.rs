@@ -0,0 +1,208 @@
+//! Dense voxel grids read from disk, for heterogeneous media.
+//!
+//! The format is a text header followed by the raw values:
+//!
+//! ```text
+//! VOXELS
+//! # comments run to the end of the line
+//! 64 32 64 2
+//! <64 · 32 · 64 · 2 little-endian f32s>
+//! ```
+//!
+//! The header holds the number of voxels along x, y and z, then the channels per voxel: density
+//! alone, or density and emission. A single whitespace byte separates it from the values, which
+//! go through the voxels with x changing fastest, then y, then z, and the channels of each
+//! voxel next to each other.
+
+use crate::{image::header_field, ray::Point3};
+
+use std::{fmt, fs, io, path::Path};
+
+#[derive(Debug)]
+pub enum VoxelError {
+    Io(io::Error),
+    Format(String),
+}
+
+impl fmt::Display for VoxelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoxelError::Io(e) => write!(f, "{e}"),
+            VoxelError::Format(message) => write!(f, "invalid voxel grid: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for VoxelError {}
+
+impl From<io::Error> for VoxelError {
+    fn from(e: io::Error) -> Self {
+        VoxelError::Io(e)
+    }
+}
+
+/// Channels a voxel can hold, in the order they are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Density = 0,
+    Emission = 1,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxelGrid {
+    dims: [usize; 3],
+    channels: usize,
+    values: Vec<f32>,
+    /// Largest density, which trilinear lookups never exceed.
+    max_density: f32,
+}
+
+impl VoxelGrid {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, VoxelError> {
+        Self::decode(&fs::read(path)?)
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, VoxelError> {
+        let err = |message: &str| VoxelError::Format(message.into());
+
+        if !data.starts_with(b"VOXELS") {
+            return Err(err("expected the `VOXELS` header"));
+        }
+        let mut pos = 6;
+
+        let mut field =
+            || header_field(data, &mut pos).ok_or_else(|| err("expected a number in the header"));
+
+        let dims = [field()?, field()?, field()?];
+        let channels = field()?;
+        if dims.contains(&0) {
+            return Err(err("empty grid"));
+        }
+        if !(1..=2).contains(&channels) {
+            return Err(err("expected 1 or 2 channels"));
+        }
+
+        let count = dims
+            .iter()
+            .try_fold(channels, |acc, &n| acc.checked_mul(n))
+            .ok_or_else(|| err("grid too large"))?;
+        let body = data.get(pos + 1..).unwrap_or_default();
+        if body.len() / 4 < count {
+            return Err(err("not enough voxel data"));
+        }
+
+        let values: Vec<f32> = body
+            .chunks_exact(4)
+            .take(count)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        if values.iter().any(|v| !(v.is_finite() && *v >= 0.)) {
+            return Err(err("values must be finite and not negative"));
+        }
+
+        let max_density = values
+            .iter()
+            .step_by(channels)
+            .fold(0., |a: f32, &b| a.max(b));
+
+        Ok(Self {
+            dims,
+            channels,
+            values,
+            max_density,
+        })
+    }
+
+    pub fn max_density(&self) -> f32 {
+        self.max_density
+    }
+
+    /// Trilinear interpolation of `channel` between voxel centers, at `p` from 0 to 1 across
+    /// the grid along every axis. Values are held constant from the outermost centers to the
+    /// faces of the grid, and missing channels are 0.
+    pub fn lookup(&self, p: Point3, channel: Channel) -> f32 {
+        let channel = channel as usize;
+        if channel >= self.channels {
+            return 0.;
+        }
+
+        let mut low = [0; 3];
+        let mut high = [0; 3];
+        let mut fraction = [0.; 3];
+        for axis in 0..3 {
+            let n = self.dims[axis];
+            let x = (p[axis] * n as f32 - 0.5).clamp(0., (n - 1) as f32);
+            low[axis] = (x as usize).min(n - 1);
+            high[axis] = (low[axis] + 1).min(n - 1);
+            fraction[axis] = x - low[axis] as f32;
+        }
+
+        let mut value = 0.;
+        for corner in 0..8 {
+            let mut weight = 1.;
+            let mut index = [0; 3];
+            for axis in 0..3 {
+                if corner & (1 << axis) == 0 {
+                    index[axis] = low[axis];
+                    weight *= 1. - fraction[axis];
+                } else {
+                    index[axis] = high[axis];
+                    weight *= fraction[axis];
+                }
+            }
+
+            if weight > 0. {
+                let voxel = (index[2] * self.dims[1] + index[1]) * self.dims[0] + index[0];
+                value += weight * self.values[voxel * self.channels + channel];
+            }
+        }
+
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Channel, VoxelGrid};
+    use crate::ray::Point3;
+
+    fn encode(header: &str, values: &[f32]) -> Vec<u8> {
+        let mut data = header.as_bytes().to_vec();
+        data.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+        data
+    }
+
+    #[test]
+    fn decode_and_lookup() {
+        // Two voxels along x, with density and emission.
+        let grid =
+            VoxelGrid::decode(&encode("VOXELS # smoke\n2 1 1 2\n", &[1., 0., 3., 5.])).unwrap();
+        assert_eq!(grid.max_density(), 3.);
+
+        let density = |x: f32| grid.lookup(Point3::new(x, 0.5, 0.5), Channel::Density);
+        assert_eq!(density(0.), 1.);
+        assert_eq!(density(0.25), 1.);
+        assert_eq!(density(0.5), 2.);
+        assert_eq!(density(0.75), 3.);
+        assert_eq!(density(1.), 3.);
+        assert_eq!(
+            grid.lookup(Point3::new(0.625, 0.1, 0.9), Channel::Emission),
+            3.75
+        );
+
+        let density_only = VoxelGrid::decode(&encode("VOXELS 1 1 1 1\n", &[2.])).unwrap();
+        assert_eq!(
+            density_only.lookup(Point3::new(0.3, 0.3, 0.3), Channel::Emission),
+            0.
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(VoxelGrid::decode(b"P6 1 1 255\n").is_err());
+        assert!(VoxelGrid::decode(&encode("VOXELS 2 2 2 1\n", &[1.; 7])).is_err());
+        assert!(VoxelGrid::decode(&encode("VOXELS 1 1 1 3\n", &[1.; 3])).is_err());
+        assert!(VoxelGrid::decode(&encode("VOXELS 0 1 1 1\n", &[])).is_err());
+        assert!(VoxelGrid::decode(&encode("VOXELS 1 1 1 1\n", &[-1.])).is_err());
+    }
+}